@@ -0,0 +1,38 @@
+//! Spams small jobs at a `JobSystem` from several submitting threads at once, which is where contention on the
+//! job queues shows. Run with `cargo bench --bench job_spam` for the lock-free job thread queues, and again with
+//! `--features mutex_job_queue` for the mutex protected queues they replaced, to compare the two.
+
+use std::{sync::Arc, thread, time::{Duration, Instant}};
+use gk_types_rs::job_system::system::JobSystem;
+
+const JOBS_PER_SUBMITTER: usize = 100_000;
+const RUNS: usize = 5;
+const QUEUE_KIND: &str = if cfg!(feature = "mutex_job_queue") { "mutex" } else { "lock-free" };
+
+fn spam(job_system: &Arc<JobSystem>, submitter_count: usize) -> Duration {
+    let start = Instant::now();
+    let submitters: Vec<_> = (0..submitter_count).map(|_| {
+        let job_system = job_system.clone();
+        thread::spawn(move || {
+            for i in 0..JOBS_PER_SUBMITTER {
+                job_system.run_job(move || i);
+            }
+        })
+    }).collect();
+    for submitter in submitters {
+        submitter.join().unwrap();
+    }
+    job_system.wait();
+    return start.elapsed();
+}
+
+fn main() {
+    let job_thread_count = thread::available_parallelism().map(|n| n.get()).unwrap_or(2).max(2);
+    let job_system = Arc::new(JobSystem::new_init(job_thread_count));
+    for submitter_count in [1, 2, 4, 8] {
+        let best = (0..RUNS).map(|_| spam(&job_system, submitter_count)).min().unwrap();
+        let total_jobs = submitter_count * JOBS_PER_SUBMITTER;
+        println!("{} queues, {} job threads, {} submitters: {} jobs in {:?} ({:.0} ns/job)",
+            QUEUE_KIND, job_thread_count, submitter_count, total_jobs, best, best.as_nanos() as f64 / total_jobs as f64);
+    }
+}
@@ -0,0 +1,129 @@
+use super::{allocator::{AllocErr, Allocator}, alloc_rc::{CountedBox, try_allocate_counted, try_allocate_counted_slice, free_counted}};
+use std::{fmt, marker::PhantomData, ops::Deref, ptr::NonNull, sync::atomic::{AtomicUsize, Ordering, fence}};
+
+/// Thread safe reference counted pointer to a value allocated through an `Allocator`.
+/// The value is dropped and the memory freed when the last `AllocArc` is dropped, on whichever thread that happens.
+/// Supports slices through `AllocArc<[T]>`.
+/// ```
+/// # use gk_types_rs::allocator::alloc_arc::AllocArc;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let arc = AllocArc::new_in(vec![1, 2, 3], global_heap_allocator());
+/// let other = arc.clone();
+/// let handle = std::thread::spawn(move || other.iter().sum::<i32>());
+/// assert_eq!(handle.join().unwrap(), 6);
+/// assert_eq!(AllocArc::strong_count(&arc), 1);
+/// ```
+pub struct AllocArc<T: ?Sized> {
+    ptr: NonNull<CountedBox<AtomicUsize, T>>,
+    allocator: Allocator,
+    marker: PhantomData<CountedBox<AtomicUsize, T>>
+}
+
+unsafe impl<T: ?Sized + Send + Sync> Send for AllocArc<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AllocArc<T> {}
+
+impl<T> AllocArc<T> {
+    /// Moves `value` into memory allocated from `allocator`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails. Use `try_new_in()` to handle the error instead.
+    pub fn new_in(value: T, allocator: &Allocator) -> Self {
+        return Self::try_new_in(value, allocator).expect("AllocArc allocation failed");
+    }
+
+    /// Moves `value` into memory allocated from `allocator`, returning an error if the allocation fails.
+    pub fn try_new_in(value: T, allocator: &Allocator) -> Result<Self, AllocErr> {
+        let ptr = try_allocate_counted(AtomicUsize::new(1), value, allocator)?;
+        return Ok(AllocArc { ptr, allocator: allocator.clone(), marker: PhantomData });
+    }
+}
+
+impl<T> AllocArc<[T]> {
+    /// Allocates a shared slice from `allocator` holding clones of every element in `elements`.
+    /// ```
+    /// # use gk_types_rs::allocator::alloc_arc::AllocArc;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let arc: AllocArc<[String]> = AllocArc::from_slice_in(&[String::from("a")], global_heap_allocator());
+    /// assert_eq!(arc[0], "a");
+    /// ```
+    pub fn from_slice_in(elements: &[T], allocator: &Allocator) -> Self
+    where T: Clone {
+        return Self::try_from_slice_in(elements, allocator).expect("AllocArc allocation failed");
+    }
+
+    /// Allocates a shared slice from `allocator` holding clones of every element in `elements`,
+    /// returning an error if the allocation fails.
+    pub fn try_from_slice_in(elements: &[T], allocator: &Allocator) -> Result<Self, AllocErr>
+    where T: Clone {
+        let ptr = try_allocate_counted_slice(AtomicUsize::new(1), elements.len(), allocator, |i| elements[i].clone())?;
+        return Ok(AllocArc { ptr, allocator: allocator.clone(), marker: PhantomData });
+    }
+}
+
+impl<T: ?Sized> AllocArc<T> {
+    /// Number of `AllocArc` pointers to the value. Other threads may change it at any time.
+    pub fn strong_count(this: &Self) -> usize {
+        return this.inner().count.load(Ordering::Acquire);
+    }
+
+    /// Checks if both pointers point to the same allocation.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        return std::ptr::addr_eq(this.ptr.as_ptr(), other.ptr.as_ptr());
+    }
+
+    /// Mutable reference to the value if there are no other `AllocArc` pointers to it.
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        if this.inner().count.load(Ordering::Acquire) != 1 {
+            return None;
+        }
+        return Some(unsafe { &mut (*this.ptr.as_ptr()).value });
+    }
+
+    /// The allocator that the value was allocated from, and will be freed to.
+    pub fn allocator(this: &Self) -> &Allocator {
+        return &this.allocator;
+    }
+
+    fn inner(&self) -> &CountedBox<AtomicUsize, T> {
+        return unsafe { self.ptr.as_ref() };
+    }
+}
+
+impl<T: ?Sized> Clone for AllocArc<T> {
+    fn clone(&self) -> Self {
+        self.inner().count.fetch_add(1, Ordering::Relaxed);
+        return AllocArc { ptr: self.ptr, allocator: self.allocator.clone(), marker: PhantomData };
+    }
+}
+
+impl<T: ?Sized> Drop for AllocArc<T> {
+    fn drop(&mut self) {
+        if self.inner().count.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        // Synchronize with every other release of the count before dropping the value.
+        fence(Ordering::Acquire);
+        unsafe { free_counted(self.ptr, &self.allocator) };
+    }
+}
+
+impl<T: ?Sized> Deref for AllocArc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return &self.inner().value;
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AllocArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Debug::fmt(&**self, f);
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for AllocArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Display::fmt(&**self, f);
+    }
+}
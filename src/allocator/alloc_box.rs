@@ -0,0 +1,193 @@
+use super::{allocator::{AllocErr, Allocator}, named_allocators::current_allocator};
+use std::{alloc::Layout, fmt, marker::PhantomData, mem::{size_of, align_of}, ops::{Deref, DerefMut}, ptr::NonNull};
+
+/// Owning pointer to a value allocated through an `Allocator`. Drops the value and frees the memory
+/// with the correct layout when dropped, so it never needs a matching `free_*` call.
+/// Supports slices through `AllocBox<[T]>`. Zero sized values do not allocate.
+/// ```
+/// # use gk_types_rs::allocator::alloc_box::AllocBox;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let mut boxed = AllocBox::new_in(String::from("hello"), global_heap_allocator());
+/// boxed.push_str(" world!");
+/// assert_eq!(*boxed, "hello world!");
+/// ```
+pub struct AllocBox<T: ?Sized> {
+    ptr: NonNull<T>,
+    allocator: Allocator,
+    marker: PhantomData<T>
+}
+
+unsafe impl<T: ?Sized + Send> Send for AllocBox<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for AllocBox<T> {}
+
+impl<T> AllocBox<T> {
+    /// Moves `value` into memory allocated from `allocator`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails. Use `try_new_in()` to handle the error instead.
+    pub fn new_in(value: T, allocator: &Allocator) -> Self {
+        return Self::try_new_in(value, allocator).expect("AllocBox allocation failed");
+    }
+
+    /// Moves `value` into memory allocated from `allocator`, returning an error if the allocation fails.
+    /// The value is dropped on failure.
+    /// ```
+    /// # use gk_types_rs::allocator::alloc_box::AllocBox;
+    /// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let (allocator, _budget) = BudgetAllocator::new_budget("tiny", global_heap_allocator(), 4);
+    /// assert!(AllocBox::try_new_in(1u32, &allocator).is_ok());
+    /// assert!(AllocBox::try_new_in(1u64, &allocator).is_err());
+    /// ```
+    pub fn try_new_in(value: T, allocator: &Allocator) -> Result<Self, AllocErr> {
+        let ptr = if size_of::<T>() == 0 {
+            NonNull::dangling()
+        }
+        else {
+            let raw = allocator.malloc_object::<T>()?;
+            unsafe { NonNull::new_unchecked(raw) }
+        };
+        unsafe { ptr.as_ptr().write(value) };
+        return Ok(AllocBox { ptr, allocator: allocator.clone(), marker: PhantomData });
+    }
+
+    /// Moves the value out of the box, freeing the memory.
+    /// ```
+    /// # use gk_types_rs::allocator::alloc_box::AllocBox;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let boxed = AllocBox::new_in(vec![1, 2, 3], global_heap_allocator());
+    /// assert_eq!(AllocBox::into_inner(boxed), vec![1, 2, 3]);
+    /// ```
+    pub fn into_inner(this: Self) -> T {
+        let value = unsafe { this.ptr.as_ptr().read() };
+        if size_of::<T>() != 0 {
+            this.allocator.free_object(this.ptr.as_ptr());
+        }
+        std::mem::forget(this);
+        return value;
+    }
+}
+
+impl<T> AllocBox<[T]> {
+    /// Allocates a slice from `allocator` holding clones of every element in `elements`.
+    /// ```
+    /// # use gk_types_rs::allocator::alloc_box::AllocBox;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let boxed: AllocBox<[String]> = AllocBox::from_slice_in(&[String::from("a"), String::from("b")], global_heap_allocator());
+    /// assert_eq!(boxed.len(), 2);
+    /// assert_eq!(boxed[1], "b");
+    /// ```
+    pub fn from_slice_in(elements: &[T], allocator: &Allocator) -> Self
+    where T: Clone {
+        return Self::try_from_slice_in(elements, allocator).expect("AllocBox allocation failed");
+    }
+
+    /// Allocates a slice from `allocator` holding clones of every element in `elements`, returning an error
+    /// if the allocation fails.
+    pub fn try_from_slice_in(elements: &[T], allocator: &Allocator) -> Result<Self, AllocErr>
+    where T: Clone {
+        return Self::try_from_fn_in(elements.len(), allocator, |i| elements[i].clone());
+    }
+
+    /// Allocates a slice of `len` elements from `allocator`, where each element is the result of `func` given its index.
+    /// ```
+    /// # use gk_types_rs::allocator::alloc_box::AllocBox;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let boxed = AllocBox::from_fn_in(4, global_heap_allocator(), |i| i * 2);
+    /// assert_eq!(&*boxed, &[0, 2, 4, 6]);
+    /// ```
+    pub fn from_fn_in<F>(len: usize, allocator: &Allocator, func: F) -> Self
+    where F: FnMut(usize) -> T {
+        return Self::try_from_fn_in(len, allocator, func).expect("AllocBox allocation failed");
+    }
+
+    /// Allocates a slice of `len` elements from `allocator`, where each element is the result of `func` given its index.
+    /// Returns an error if the allocation fails.
+    pub fn try_from_fn_in<F>(len: usize, allocator: &Allocator, func: F) -> Result<Self, AllocErr>
+    where F: FnMut(usize) -> T {
+        let ptr = allocator.malloc_slice_with(len, align_of::<T>(), func)?;
+        return Ok(AllocBox { ptr, allocator: allocator.clone(), marker: PhantomData });
+    }
+
+    /// Allocates a slice of `len` default constructed elements from `allocator`.
+    pub fn new_default_slice_in(len: usize, allocator: &Allocator) -> Self
+    where T: Default {
+        return Self::from_fn_in(len, allocator, |_| T::default());
+    }
+}
+
+impl<T: ?Sized> AllocBox<T> {
+    /// The allocator that the value was allocated from, and will be freed to.
+    pub fn allocator(this: &Self) -> &Allocator {
+        return &this.allocator;
+    }
+
+    /// Const pointer to the held value.
+    pub fn as_ptr(this: &Self) -> *const T {
+        return this.ptr.as_ptr();
+    }
+}
+
+impl<T: ?Sized> Drop for AllocBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let layout = Layout::for_value(self.ptr.as_ref());
+            self.ptr.as_ptr().drop_in_place();
+            if layout.size() != 0 {
+                self.allocator.free(self.ptr.as_ptr() as *mut u8, layout);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for AllocBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return unsafe { self.ptr.as_ref() };
+    }
+}
+
+impl<T: ?Sized> DerefMut for AllocBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        return unsafe { self.ptr.as_mut() };
+    }
+}
+
+/// Allocates from the calling thread's current allocator. See `push_current_allocator()`.
+impl<T: Default> Default for AllocBox<T> {
+    fn default() -> Self {
+        return AllocBox::new_in(T::default(), &current_allocator());
+    }
+}
+
+impl<T: Clone> Clone for AllocBox<T> {
+    fn clone(&self) -> Self {
+        return AllocBox::new_in((**self).clone(), &self.allocator);
+    }
+}
+
+impl<T: Clone> Clone for AllocBox<[T]> {
+    fn clone(&self) -> Self {
+        return AllocBox::from_slice_in(self, &self.allocator);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AllocBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Debug::fmt(&**self, f);
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for AllocBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Display::fmt(&**self, f);
+    }
+}
+
+impl<T: ?Sized + PartialEq> PartialEq for AllocBox<T> {
+    fn eq(&self, other: &Self) -> bool {
+        return **self == **other;
+    }
+}
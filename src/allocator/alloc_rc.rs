@@ -0,0 +1,198 @@
+use super::allocator::{AllocErr, Allocator};
+use std::{alloc::Layout, cell::Cell, fmt, marker::PhantomData, mem::{size_of, align_of}, ops::Deref, ptr::NonNull};
+
+/// Reference count followed by the value, shared by `AllocRc` and `AllocArc` with different count types.
+#[repr(C)]
+pub(super) struct CountedBox<C, T: ?Sized> {
+    pub(super) count: C,
+    pub(super) value: T
+}
+
+/// Allocates a counted box holding `value` from `allocator`.
+pub(super) fn try_allocate_counted<C, T>(count: C, value: T, allocator: &Allocator) -> Result<NonNull<CountedBox<C, T>>, AllocErr> {
+    let raw = allocator.malloc_object::<CountedBox<C, T>>()?;
+    unsafe {
+        raw.write(CountedBox { count, value });
+        return Ok(NonNull::new_unchecked(raw));
+    }
+}
+
+/// Allocates a counted box holding a slice of `len` elements from `allocator`, where each element is the result of `func`.
+pub(super) fn try_allocate_counted_slice<C, T, F>(count: C, len: usize, allocator: &Allocator, mut func: F) -> Result<NonNull<CountedBox<C, [T]>>, AllocErr>
+where F: FnMut(usize) -> T {
+    let invalid_layout = AllocErr::InvalidLayout {
+        size: size_of::<T>().saturating_mul(len),
+        align: align_of::<T>(),
+        allocator: allocator.name()
+    };
+    let array_layout = Layout::array::<T>(len).map_err(|_| invalid_layout)?;
+    let (layout, value_offset) = Layout::new::<C>().extend(array_layout).map_err(|_| invalid_layout)?;
+    let layout = layout.pad_to_align();
+    let raw = allocator.malloc(layout)?;
+
+    // Drops the already written elements and frees the memory if `func` panics.
+    struct PartialSlice<'a, T> { raw: *mut u8, data: *mut T, initialized: usize, layout: Layout, allocator: &'a Allocator }
+    impl<'a, T> Drop for PartialSlice<'a, T> {
+        fn drop(&mut self) {
+            unsafe { std::ptr::slice_from_raw_parts_mut(self.data, self.initialized).drop_in_place() };
+            self.allocator.free(self.raw, self.layout);
+        }
+    }
+
+    unsafe {
+        (raw as *mut C).write(count);
+        let data = raw.add(value_offset) as *mut T;
+        let mut partial = PartialSlice { raw, data, initialized: 0, layout, allocator };
+        for i in 0..len {
+            data.add(i).write(func(i));
+            partial.initialized += 1;
+        }
+        std::mem::forget(partial);
+        // The fat pointer metadata of the slice is the element count, which carries over to the counted box.
+        let boxed = std::ptr::slice_from_raw_parts_mut(raw as *mut T, len) as *mut CountedBox<C, [T]>;
+        return Ok(NonNull::new_unchecked(boxed));
+    }
+}
+
+/// Drops the value within a counted box, and frees the memory back to `allocator`.
+pub(super) unsafe fn free_counted<C, T: ?Sized>(ptr: NonNull<CountedBox<C, T>>, allocator: &Allocator) {
+    let layout = Layout::for_value(ptr.as_ref());
+    ptr.as_ptr().drop_in_place();
+    allocator.free(ptr.as_ptr() as *mut u8, layout);
+}
+
+/// Single threaded reference counted pointer to a value allocated through an `Allocator`.
+/// The value is dropped and the memory freed when the last `AllocRc` is dropped.
+/// Supports slices through `AllocRc<[T]>`.
+/// ```
+/// # use gk_types_rs::allocator::alloc_rc::AllocRc;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let rc = AllocRc::new_in(String::from("shared"), global_heap_allocator());
+/// let other = rc.clone();
+/// assert_eq!(AllocRc::strong_count(&rc), 2);
+/// assert!(AllocRc::ptr_eq(&rc, &other));
+/// drop(other);
+/// assert_eq!(AllocRc::strong_count(&rc), 1);
+/// assert_eq!(*rc, "shared");
+/// ```
+pub struct AllocRc<T: ?Sized> {
+    ptr: NonNull<CountedBox<Cell<usize>, T>>,
+    allocator: Allocator,
+    marker: PhantomData<CountedBox<Cell<usize>, T>>
+}
+
+impl<T> AllocRc<T> {
+    /// Moves `value` into memory allocated from `allocator`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails. Use `try_new_in()` to handle the error instead.
+    pub fn new_in(value: T, allocator: &Allocator) -> Self {
+        return Self::try_new_in(value, allocator).expect("AllocRc allocation failed");
+    }
+
+    /// Moves `value` into memory allocated from `allocator`, returning an error if the allocation fails.
+    pub fn try_new_in(value: T, allocator: &Allocator) -> Result<Self, AllocErr> {
+        let ptr = try_allocate_counted(Cell::new(1), value, allocator)?;
+        return Ok(AllocRc { ptr, allocator: allocator.clone(), marker: PhantomData });
+    }
+}
+
+impl<T> AllocRc<[T]> {
+    /// Allocates a shared slice from `allocator` holding clones of every element in `elements`.
+    /// ```
+    /// # use gk_types_rs::allocator::alloc_rc::AllocRc;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let rc: AllocRc<[u32]> = AllocRc::from_slice_in(&[1, 2, 3], global_heap_allocator());
+    /// let other = rc.clone();
+    /// assert_eq!(&*other, &[1, 2, 3]);
+    /// ```
+    pub fn from_slice_in(elements: &[T], allocator: &Allocator) -> Self
+    where T: Clone {
+        return Self::try_from_slice_in(elements, allocator).expect("AllocRc allocation failed");
+    }
+
+    /// Allocates a shared slice from `allocator` holding clones of every element in `elements`,
+    /// returning an error if the allocation fails.
+    pub fn try_from_slice_in(elements: &[T], allocator: &Allocator) -> Result<Self, AllocErr>
+    where T: Clone {
+        let ptr = try_allocate_counted_slice(Cell::new(1), elements.len(), allocator, |i| elements[i].clone())?;
+        return Ok(AllocRc { ptr, allocator: allocator.clone(), marker: PhantomData });
+    }
+}
+
+impl<T: ?Sized> AllocRc<T> {
+    /// Number of `AllocRc` pointers to the value.
+    pub fn strong_count(this: &Self) -> usize {
+        return this.inner().count.get();
+    }
+
+    /// Checks if both pointers point to the same allocation.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        return std::ptr::addr_eq(this.ptr.as_ptr(), other.ptr.as_ptr());
+    }
+
+    /// Mutable reference to the value if there are no other `AllocRc` pointers to it.
+    /// ```
+    /// # use gk_types_rs::allocator::alloc_rc::AllocRc;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let mut rc = AllocRc::new_in(1, global_heap_allocator());
+    /// *AllocRc::get_mut(&mut rc).unwrap() = 2;
+    /// let other = rc.clone();
+    /// assert!(AllocRc::get_mut(&mut rc).is_none());
+    /// assert_eq!(*other, 2);
+    /// ```
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        if Self::strong_count(this) != 1 {
+            return None;
+        }
+        return Some(unsafe { &mut (*this.ptr.as_ptr()).value });
+    }
+
+    /// The allocator that the value was allocated from, and will be freed to.
+    pub fn allocator(this: &Self) -> &Allocator {
+        return &this.allocator;
+    }
+
+    fn inner(&self) -> &CountedBox<Cell<usize>, T> {
+        return unsafe { self.ptr.as_ref() };
+    }
+}
+
+impl<T: ?Sized> Clone for AllocRc<T> {
+    fn clone(&self) -> Self {
+        let count = &self.inner().count;
+        count.set(count.get() + 1);
+        return AllocRc { ptr: self.ptr, allocator: self.allocator.clone(), marker: PhantomData };
+    }
+}
+
+impl<T: ?Sized> Drop for AllocRc<T> {
+    fn drop(&mut self) {
+        let count = &self.inner().count;
+        count.set(count.get() - 1);
+        if count.get() == 0 {
+            unsafe { free_counted(self.ptr, &self.allocator) };
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for AllocRc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return &self.inner().value;
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AllocRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Debug::fmt(&**self, f);
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for AllocRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Display::fmt(&**self, f);
+    }
+}
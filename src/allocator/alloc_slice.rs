@@ -0,0 +1,125 @@
+use super::allocator::{AllocErr, Allocator};
+use std::{fmt, marker::PhantomData, mem::align_of, ops::{Deref, DerefMut}, ptr::NonNull};
+
+/// Owning, fixed length slice allocated through an `Allocator` with a chosen alignment, such as 64 bytes
+/// for SIMD or cache line sized data. Drops the elements and frees the memory with the same alignment when dropped.
+/// Zero sized slices do not allocate.
+/// ```
+/// # use gk_types_rs::allocator::alloc_slice::AllocSlice;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let mut slice = AllocSlice::from_fn_in(16, 64, global_heap_allocator(), |i| i as f32);
+/// assert_eq!(slice.as_ptr() as usize % 64, 0);
+/// slice[3] *= 2.0;
+/// assert_eq!(slice.iter().sum::<f32>(), 123.0);
+/// ```
+pub struct AllocSlice<T> {
+    ptr: NonNull<[T]>,
+    byte_alignment: usize,
+    allocator: Allocator,
+    marker: PhantomData<T>
+}
+
+unsafe impl<T: Send> Send for AllocSlice<T> {}
+unsafe impl<T: Sync> Sync for AllocSlice<T> {}
+
+impl<T> AllocSlice<T> {
+    /// Allocates `len` elements aligned to `byte_alignment` from `allocator`, where each element is the result
+    /// of `func` given its index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails. Use `try_from_fn_in()` to handle the error instead.
+    pub fn from_fn_in<F>(len: usize, byte_alignment: usize, allocator: &Allocator, func: F) -> Self
+    where F: FnMut(usize) -> T {
+        return Self::try_from_fn_in(len, byte_alignment, allocator, func).expect("AllocSlice allocation failed");
+    }
+
+    /// Allocates `len` elements aligned to `byte_alignment` from `allocator`, where each element is the result
+    /// of `func` given its index. Returns an error if the allocation fails, or if `byte_alignment` is not
+    /// a power of two of at least `align_of::<T>()`.
+    /// ```
+    /// # use gk_types_rs::allocator::alloc_slice::AllocSlice;
+    /// # use gk_types_rs::allocator::allocator::AllocErr;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let result = AllocSlice::try_from_fn_in(4, 2, global_heap_allocator(), |i| i as u64);
+    /// assert!(matches!(result, Err(AllocErr::InvalidLayout { .. })));
+    ///
+    /// // Zero sized types never allocate.
+    /// let units = AllocSlice::try_from_fn_in(1000, 1, global_heap_allocator(), |_| ()).unwrap();
+    /// assert_eq!(units.len(), 1000);
+    /// ```
+    pub fn try_from_fn_in<F>(len: usize, byte_alignment: usize, allocator: &Allocator, func: F) -> Result<Self, AllocErr>
+    where F: FnMut(usize) -> T {
+        let ptr = allocator.malloc_slice_with(len, byte_alignment, func)?;
+        return Ok(AllocSlice { ptr, byte_alignment, allocator: allocator.clone(), marker: PhantomData });
+    }
+
+    /// Allocates `len` default constructed elements from `allocator`, with the alignment of `T`.
+    pub fn new_default_in(len: usize, allocator: &Allocator) -> Self
+    where T: Default {
+        return Self::from_fn_in(len, align_of::<T>(), allocator, |_| T::default());
+    }
+
+    /// Allocates a slice from `allocator` holding clones of every element in `elements`, with the alignment of `T`.
+    /// ```
+    /// # use gk_types_rs::allocator::alloc_slice::AllocSlice;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let slice = AllocSlice::from_slice_in(&["a", "b"], global_heap_allocator());
+    /// assert_eq!(&*slice, &["a", "b"]);
+    /// ```
+    pub fn from_slice_in(elements: &[T], allocator: &Allocator) -> Self
+    where T: Clone {
+        return Self::from_fn_in(elements.len(), align_of::<T>(), allocator, |i| elements[i].clone());
+    }
+
+    /// Alignment in bytes that the slice was allocated with.
+    pub fn alignment(this: &Self) -> usize {
+        return this.byte_alignment;
+    }
+
+    /// The allocator that the slice was allocated from, and will be freed to.
+    pub fn allocator(this: &Self) -> &Allocator {
+        return &this.allocator;
+    }
+}
+
+impl<T> Drop for AllocSlice<T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.ptr.as_ptr().drop_in_place();
+            self.allocator.free_slice(self.ptr, self.byte_alignment);
+        }
+    }
+}
+
+impl<T> Deref for AllocSlice<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        return unsafe { self.ptr.as_ref() };
+    }
+}
+
+impl<T> DerefMut for AllocSlice<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        return unsafe { self.ptr.as_mut() };
+    }
+}
+
+impl<T: Clone> Clone for AllocSlice<T> {
+    fn clone(&self) -> Self {
+        return AllocSlice::from_fn_in(self.len(), self.byte_alignment, &self.allocator, |i| self[i].clone());
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AllocSlice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Debug::fmt(&**self, f);
+    }
+}
+
+impl<T: PartialEq> PartialEq for AllocSlice<T> {
+    fn eq(&self, other: &Self) -> bool {
+        return **self == **other;
+    }
+}
@@ -1,10 +1,83 @@
-use std::{alloc::Layout, mem::{size_of, align_of}, sync::Arc};
+use std::{alloc::Layout, fmt, mem::{size_of, align_of}, ptr::{self, NonNull}, sync::{Arc, Mutex, Weak}};
 
-#[derive(Debug)]
+/// Reason an allocation failed, along with the failing request and the name of the allocator that refused it.
+/// ```
+/// # use gk_types_rs::allocator::allocator::AllocErr;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// // size_of::<u64>() * usize::MAX overflows.
+/// let err = global_heap_allocator().malloc_buffer::<u64>(usize::MAX).unwrap_err();
+/// assert!(matches!(err, AllocErr::InvalidLayout { .. }));
+/// assert_eq!(err.allocator_name(), "HeapAllocator");
+/// assert!(err.layout().is_none());
+/// ```
+/// Wrapping allocators report their own kind of failure.
+/// ```
+/// # use gk_types_rs::allocator::allocator::AllocErr;
+/// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let (allocator, _budget) = BudgetAllocator::new_budget("audio", global_heap_allocator(), 64);
+/// let err = allocator.malloc_buffer::<u8>(128).unwrap_err();
+/// assert_eq!(err, AllocErr::BudgetExceeded { layout: err.layout().unwrap(), allocator: "audio", used: 0, limit: 64 });
+/// assert_eq!(err.to_string(), "allocating 128 bytes would exceed budget audio (0 of 64 bytes used)");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AllocErr {
-    OutOfMemory
+    /// The allocator, or the system beneath it, could not provide the memory.
+    OutOfMemory { layout: Layout, allocator: &'static str },
+    /// The requested size and alignment do not form a valid layout, such as a size that overflows
+    /// or an alignment that is not a power of two. An overflowing size is reported as `usize::MAX`.
+    InvalidLayout { size: usize, align: usize, allocator: &'static str },
+    /// The allocator cannot provide memory aligned as requested.
+    AlignmentUnsupported { layout: Layout, allocator: &'static str },
+    /// The allocation would take a budget past its byte limit.
+    BudgetExceeded { layout: Layout, allocator: &'static str, used: usize, limit: usize },
+    /// An allocator with a fixed amount of memory, such as an arena, does not have enough left.
+    Exhausted { layout: Layout, allocator: &'static str }
+}
+
+impl AllocErr {
+    /// Layout of the failed allocation. None for `InvalidLayout`, since no valid layout exists.
+    pub fn layout(&self) -> Option<Layout> {
+        return match self {
+            AllocErr::OutOfMemory { layout, .. } => Some(*layout),
+            AllocErr::InvalidLayout { .. } => None,
+            AllocErr::AlignmentUnsupported { layout, .. } => Some(*layout),
+            AllocErr::BudgetExceeded { layout, .. } => Some(*layout),
+            AllocErr::Exhausted { layout, .. } => Some(*layout)
+        };
+    }
+
+    /// Name of the allocator that refused the allocation.
+    pub fn allocator_name(&self) -> &'static str {
+        return match self {
+            AllocErr::OutOfMemory { allocator, .. } => allocator,
+            AllocErr::InvalidLayout { allocator, .. } => allocator,
+            AllocErr::AlignmentUnsupported { allocator, .. } => allocator,
+            AllocErr::BudgetExceeded { allocator, .. } => allocator,
+            AllocErr::Exhausted { allocator, .. } => allocator
+        };
+    }
+}
+
+impl fmt::Display for AllocErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            AllocErr::OutOfMemory { layout, allocator } =>
+                write!(f, "{} is out of memory allocating {} bytes aligned to {}", allocator, layout.size(), layout.align()),
+            AllocErr::InvalidLayout { size, align, allocator } =>
+                write!(f, "{} was given an invalid layout of {} bytes aligned to {}", allocator, size, align),
+            AllocErr::AlignmentUnsupported { layout, allocator } =>
+                write!(f, "{} does not support an alignment of {} (allocating {} bytes)", allocator, layout.align(), layout.size()),
+            AllocErr::BudgetExceeded { layout, allocator, used, limit } =>
+                write!(f, "allocating {} bytes would exceed budget {} ({} of {} bytes used)", layout.size(), allocator, used, limit),
+            AllocErr::Exhausted { layout, allocator } =>
+                write!(f, "{} does not have {} bytes aligned to {} left", allocator, layout.size(), layout.align())
+        };
+    }
 }
 
+impl std::error::Error for AllocErr {}
+
 /// Basically a wrapper around Arc<Box<dyn AllocatorTrait>> with helper methods.
 /// ```
 /// # use gk_types_rs::allocator::allocator::Allocator;
@@ -17,123 +90,304 @@ pub struct Allocator {
 }
 
 impl Allocator {
+    /// Wraps an already boxed allocator implementation in a shareable `Allocator` handle.
+    /// Used by allocators that need construction parameters, where `AllocatorTrait::new()` cannot be used.
+    pub fn from_boxed(inner: Box<dyn AllocatorTrait>) -> Allocator {
+        let inner = Arc::new(inner);
+        let mut registered = REGISTERED_ALLOCATORS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Dropped allocators are only pruned when the registry would otherwise grow, and then it is left at least
+        // half empty, so registering stays amortized O(1) while the registry stays within twice the live allocators.
+        if registered.len() == registered.capacity() {
+            registered.retain(|allocator| allocator.0.strong_count() > 0);
+            let live_count = registered.len();
+            registered.reserve(live_count.max(4));
+        }
+        registered.push(RegisteredAllocator(Arc::downgrade(&inner)));
+        return Allocator { inner };
+    }
+
+    /// Every allocator that is currently alive, in creation order. Meant for tooling, such as memory overlays
+    /// listing each allocator's usage.
+    /// ```
+    /// # use gk_types_rs::allocator::allocator::Allocator;
+    /// # use gk_types_rs::allocator::arena_allocator::ArenaAllocator;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let arena = ArenaAllocator::new_arena(global_heap_allocator(), 1024);
+    /// let a = arena.malloc_buffer::<u8>(100).unwrap();
+    ///
+    /// for allocator in Allocator::live_allocators() {
+    ///     println!("{}: {:?} of {:?}", allocator.name(), allocator.stats(), allocator.capacity());
+    /// }
+    /// let arenas: Vec<Allocator> = Allocator::live_allocators().into_iter().filter(|allocator| allocator.ptr_eq(&arena)).collect();
+    /// assert_eq!(arenas[0].stats().unwrap().used_bytes, 100);
+    /// assert_eq!(arenas[0].capacity(), Some(1024));
+    /// # arena.free_buffer(a, 100);
+    /// ```
+    pub fn live_allocators() -> Vec<Allocator> {
+        let mut registered = REGISTERED_ALLOCATORS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        registered.retain(|allocator| allocator.0.strong_count() > 0);
+        return registered.iter().filter_map(|allocator| allocator.0.upgrade()).map(|inner| Allocator { inner }).collect();
+    }
+
+    /// Checks if both handles refer to the same allocator.
+    pub fn ptr_eq(&self, other: &Allocator) -> bool {
+        return Arc::ptr_eq(&self.inner, &other.inner);
+    }
+
+    /// Allocates raw bytes for `layout` directly through the underlying allocator.
+    pub fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        return self.inner.malloc(layout);
+    }
+
+    /// Allocates raw zeroed bytes for `layout` directly through the underlying allocator.
+    pub fn malloc_zero(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        return self.inner.malloc_zero(layout);
+    }
+
+    /// Frees raw bytes that were allocated with `layout` through this allocator.
+    pub fn free(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.free(ptr, layout);
+    }
+
+    /// Checks if `ptr` was allocated by this allocator. See `AllocatorTrait::owns()`.
+    pub fn owns(&self, ptr: *const u8) -> bool {
+        return self.inner.owns(ptr);
+    }
+
+    /// Tries to grow the allocation at `ptr` to `new_size` bytes without moving it. See `AllocatorTrait::grow_in_place()`.
+    pub fn grow_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        return self.inner.grow_in_place(ptr, layout, new_size);
+    }
+
+    /// Tries to shrink the allocation at `ptr` to `new_size` bytes without moving it. See `AllocatorTrait::shrink_in_place()`.
+    pub fn shrink_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        return self.inner.shrink_in_place(ptr, layout, new_size);
+    }
+
     pub fn malloc_object<T>(&self) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>(), align_of::<T>()) };
-        let byte_buffer = self.inner.malloc(layout)?;
-        return Ok(byte_buffer as *mut T);
+        return self.malloc_typed::<T>(1, align_of::<T>(), false);
     }
 
     pub fn malloc_object_zero<T>(&self) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>(), align_of::<T>()) };
-        let byte_buffer = self.inner.malloc_zero(layout)?;
-        return Ok(byte_buffer as *mut T);
+        return self.malloc_typed::<T>(1, align_of::<T>(), true);
     }
 
     pub fn malloc_object_default<T: Default>(&self) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>(), align_of::<T>()) };
-        let byte_buffer = self.inner.malloc(layout)?;
-        let type_buffer = byte_buffer as *mut T;
-        unsafe { std::mem::swap(&mut *type_buffer, &mut T::default()); }
+        let value = T::default();
+        let type_buffer = self.malloc_typed::<T>(1, align_of::<T>(), false)?;
+        unsafe { type_buffer.write(value); }
         return Ok(type_buffer);
     }
 
     pub fn malloc_aligned_object<T>(&self, byte_alignment: usize) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>(), byte_alignment) };
-        let byte_buffer = self.inner.malloc(layout)?;
-        return Ok(byte_buffer as *mut T);
+        return self.malloc_typed::<T>(1, byte_alignment, false);
     }
 
     pub fn malloc_aligned_object_zero<T>(&self, byte_alignment: usize) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>(), byte_alignment) };
-        let byte_buffer = self.inner.malloc_zero(layout)?;
-        return Ok(byte_buffer as *mut T);
+        return self.malloc_typed::<T>(1, byte_alignment, true);
     }
 
     pub fn malloc_aligned_object_default<T: Default>(&self, byte_alignment: usize) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>(), byte_alignment) };
-        let byte_buffer = self.inner.malloc(layout)?;
-        let type_buffer = byte_buffer as *mut T;
-        unsafe { std::mem::swap(&mut *type_buffer, &mut T::default()); }
+        let value = T::default();
+        let type_buffer = self.malloc_typed::<T>(1, byte_alignment, false)?;
+        unsafe { type_buffer.write(value); }
         return Ok(type_buffer);
     }
 
     pub fn malloc_buffer<T>(&self, num_elements: usize) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>() * num_elements, align_of::<T>()) };
-        let byte_buffer = self.inner.malloc(layout)?;
-        return Ok(byte_buffer as *mut T);
+        return self.malloc_typed::<T>(num_elements, align_of::<T>(), false);
     }
 
     pub fn malloc_buffer_zero<T>(&self, num_elements: usize) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>() * num_elements, align_of::<T>()) };
-        let byte_buffer = self.inner.malloc_zero(layout)?;
-        return Ok(byte_buffer as *mut T);  
+        return self.malloc_typed::<T>(num_elements, align_of::<T>(), true);
     }
 
     pub fn malloc_buffer_default<T: Default>(&self, num_elements: usize) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>() * num_elements, align_of::<T>()) };
-        let byte_buffer = self.inner.malloc(layout)?;
-        let type_buffer = byte_buffer as *mut T;
-        unsafe { 
-            for i in 0..num_elements as isize {
-                std::mem::swap(&mut *type_buffer.offset(i), &mut T::default()); 
-            }      
-        }
-        return Ok(type_buffer);
+        let slice = self.malloc_slice_with(num_elements, align_of::<T>(), |_| T::default())?;
+        return Ok(slice.as_ptr() as *mut T);
     }
 
     pub fn malloc_aligned_buffer<T>(&self, num_elements: usize, byte_alignment: usize) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>() * num_elements, byte_alignment) };
-        let byte_buffer = self.inner.malloc(layout)?;
-        return Ok(byte_buffer as *mut T);
+        return self.malloc_typed::<T>(num_elements, byte_alignment, false);
     }
 
     pub fn malloc_aligned_buffer_zero<T>(&self, num_elements: usize, byte_alignment: usize) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>() * num_elements, byte_alignment) };
-        let byte_buffer = self.inner.malloc_zero(layout)?;
-        return Ok(byte_buffer as *mut T);
+        return self.malloc_typed::<T>(num_elements, byte_alignment, true);
     }
 
     pub fn malloc_aligned_buffer_default<T: Default>(&self, num_elements: usize, byte_alignment: usize) -> Result<*mut T, AllocErr> {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>() * num_elements, byte_alignment) };
-        let byte_buffer = self.inner.malloc(layout)?;
-        let type_buffer = byte_buffer as *mut T;
-        unsafe { 
-            for i in 0..num_elements as isize {
-                std::mem::swap(&mut *type_buffer.offset(i), &mut T::default()); 
-            }      
-        }
-        return Ok(type_buffer);
+        let slice = self.malloc_slice_with(num_elements, byte_alignment, |_| T::default())?;
+        return Ok(slice.as_ptr() as *mut T);
     }
 
     pub fn free_object<T>(&self, object: *mut T) {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>(), align_of::<T>()) };
-        self.inner.free(object as *mut u8, layout);
+        self.free_typed(object, 1, align_of::<T>());
     }
 
     pub fn free_object_aligned<T>(&self, object: *mut T, byte_alignment: usize) {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>(), byte_alignment) };
-        self.inner.free(object as *mut u8, layout);
+        self.free_typed(object, 1, byte_alignment);
     }
 
     pub fn free_buffer<T>(&self, buffer: *mut T, num_elements: usize) {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>() * num_elements, align_of::<T>()) };
-        self.inner.free(buffer as *mut u8, layout);
+        self.free_typed(buffer, num_elements, align_of::<T>());
     }
 
     pub fn free_aligned_buffer<T>(&self, buffer: *mut T, num_elements: usize, byte_alignment: usize) {
-        let layout = unsafe { Layout::from_size_align_unchecked(size_of::<T>() * num_elements, byte_alignment) };
-        self.inner.free(buffer as *mut u8, layout);
+        self.free_typed(buffer, num_elements, byte_alignment);
     }
 
-}
+    /// Allocates uninitialized memory for `len` T's aligned to `byte_alignment`.
+    /// Returns `AllocErr::InvalidLayout` if the size overflows, or if `byte_alignment` is not a power of two
+    /// or is less than `align_of::<T>()`. Zero sized requests do not allocate, and return a dangling, aligned slice.
+    /// Free with `free_slice()` using the same alignment.
+    /// ```
+    /// # use gk_types_rs::allocator::allocator::AllocErr;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let allocator = global_heap_allocator();
+    /// let slice = allocator.malloc_slice::<f32>(16, 64).unwrap();
+    /// assert_eq!(slice.len(), 16);
+    /// assert_eq!(slice.as_ptr() as *mut f32 as usize % 64, 0);
+    /// unsafe { allocator.free_slice(slice, 64) };
+    ///
+    /// assert!(matches!(allocator.malloc_slice::<u64>(usize::MAX, 8), Err(AllocErr::InvalidLayout { .. })));
+    /// assert!(matches!(allocator.malloc_slice::<u64>(1, 4), Err(AllocErr::InvalidLayout { .. })));
+    /// assert!(matches!(allocator.malloc_slice::<u64>(1, 24), Err(AllocErr::InvalidLayout { .. })));
+    /// ```
+    pub fn malloc_slice<T>(&self, len: usize, byte_alignment: usize) -> Result<NonNull<[T]>, AllocErr> {
+        let data = self.malloc_typed::<T>(len, byte_alignment, false)?;
+        return Ok(unsafe { NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(data, len)) });
+    }
 
-unsafe impl Sync for Allocator {}
+    /// Allocates zeroed memory for `len` T's aligned to `byte_alignment`. See `malloc_slice()`.
+    pub fn malloc_slice_zero<T>(&self, len: usize, byte_alignment: usize) -> Result<NonNull<[T]>, AllocErr> {
+        let data = self.malloc_typed::<T>(len, byte_alignment, true)?;
+        return Ok(unsafe { NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(data, len)) });
+    }
 
-pub trait AllocatorTrait {
+    /// Allocates memory for `len` T's aligned to `byte_alignment`, where each element is initialized to the
+    /// result of `func` given its index. If `func` panics, the already written elements are dropped and
+    /// the memory is freed. See `malloc_slice()`.
+    /// ```
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let allocator = global_heap_allocator();
+    /// let slice = allocator.malloc_slice_with(3, 8, |i| i.to_string()).unwrap();
+    /// unsafe {
+    ///     assert_eq!(slice.as_ref(), &["0", "1", "2"]);
+    ///     slice.as_ptr().drop_in_place();
+    ///     allocator.free_slice(slice, 8);
+    /// }
+    /// ```
+    pub fn malloc_slice_with<T, F>(&self, len: usize, byte_alignment: usize, mut func: F) -> Result<NonNull<[T]>, AllocErr>
+    where F: FnMut(usize) -> T {
+        let data = self.malloc_typed::<T>(len, byte_alignment, false)?;
+
+        // Drops the already written elements and frees the memory if `func` panics.
+        struct PartialSlice<'a, T> { data: *mut T, initialized: usize, len: usize, byte_alignment: usize, allocator: &'a Allocator }
+        impl<'a, T> Drop for PartialSlice<'a, T> {
+            fn drop(&mut self) {
+                unsafe { ptr::slice_from_raw_parts_mut(self.data, self.initialized).drop_in_place() };
+                self.allocator.free_typed(self.data, self.len, self.byte_alignment);
+            }
+        }
+
+        let mut partial = PartialSlice { data, initialized: 0, len, byte_alignment, allocator: self };
+        for i in 0..len {
+            unsafe { data.add(i).write(func(i)) };
+            partial.initialized += 1;
+        }
+        std::mem::forget(partial);
+        return Ok(unsafe { NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(data, len)) });
+    }
+
+    /// Frees a slice allocated by `malloc_slice()`, `malloc_slice_zero()` or `malloc_slice_with()`.
+    /// The elements are not dropped.
+    ///
+    /// # Safety
+    ///
+    /// `slice` must have been allocated by this allocator with the same length and `byte_alignment`,
+    /// and must not be used afterwards.
+    pub unsafe fn free_slice<T>(&self, slice: NonNull<[T]>, byte_alignment: usize) {
+        self.free_typed(slice.as_ptr() as *mut T, slice.len(), byte_alignment);
+    }
+
+    /// Name of the underlying allocator. See `AllocatorTrait::name()`.
+    pub fn name(&self) -> &'static str {
+        return self.inner.name();
+    }
+
+    /// Current usage of the underlying allocator. See `AllocatorTrait::stats()`.
+    pub fn stats(&self) -> Option<AllocatorStats> {
+        return self.inner.stats();
+    }
+
+    /// Total bytes the underlying allocator can hand out. See `AllocatorTrait::capacity()`.
+    pub fn capacity(&self) -> Option<usize> {
+        return self.inner.capacity();
+    }
+
+    /// Bytes actually usable at `ptr`, which was allocated with `layout`. See `AllocatorTrait::usable_size()`.
+    pub fn usable_size(&self, ptr: *const u8, layout: Layout) -> usize {
+        return self.inner.usable_size(ptr, layout);
+    }
+
+    /// Layout of `num_elements` T's aligned to `byte_alignment`, checking for size overflow and that the alignment
+    /// is a power of two of at least `align_of::<T>()`.
+    fn buffer_layout<T>(&self, num_elements: usize, byte_alignment: usize) -> Result<Layout, AllocErr> {
+        let size = size_of::<T>().checked_mul(num_elements);
+        if byte_alignment >= align_of::<T>() {
+            if let Some(layout) = size.and_then(|size| Layout::from_size_align(size, byte_alignment).ok()) {
+                return Ok(layout);
+            }
+        }
+        return Err(AllocErr::InvalidLayout { size: size.unwrap_or(usize::MAX), align: byte_alignment, allocator: self.name() });
+    }
+
+    /// Allocates `num_elements` T's aligned to `byte_alignment`. Zero sized requests return a dangling pointer
+    /// with the requested alignment, without allocating.
+    fn malloc_typed<T>(&self, num_elements: usize, byte_alignment: usize, zeroed: bool) -> Result<*mut T, AllocErr> {
+        let layout = self.buffer_layout::<T>(num_elements, byte_alignment)?;
+        if layout.size() == 0 {
+            return Ok(layout.align() as *mut T);
+        }
+        let byte_buffer = if zeroed { self.inner.malloc_zero(layout)? } else { self.inner.malloc(layout)? };
+        return Ok(byte_buffer as *mut T);
+    }
+
+    /// Frees memory from `malloc_typed()`. Does nothing for zero sized requests, as they were never allocated.
+    fn free_typed<T>(&self, buffer: *mut T, num_elements: usize, byte_alignment: usize) {
+        let layout = self.buffer_layout::<T>(num_elements, byte_alignment).expect("freeing with an invalid layout");
+        if layout.size() != 0 {
+            self.inner.free(buffer as *mut u8, layout);
+        }
+    }
+}
+
+/// Implemented by every allocator behind an `Allocator` handle. Handles are shared across threads, such as with job
+/// threads, so implementations must be safe to use from any thread.
+/// ``` compile_fail
+/// # use gk_types_rs::allocator::allocator::{AllocatorTrait, AllocErr};
+/// # use std::{alloc::Layout, cell::Cell};
+/// // A `Cell` counter is not safe to share between threads, so this does not compile.
+/// struct CountingAllocator { count: Cell<usize> }
+///
+/// impl AllocatorTrait for CountingAllocator {
+///     fn new_impl() -> Box<dyn AllocatorTrait> {
+///         return Box::new(CountingAllocator { count: Cell::new(0) });
+///     }
+///
+///     fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+///         self.count.set(self.count.get() + 1);
+///         return Err(AllocErr::OutOfMemory { layout, allocator: "CountingAllocator" });
+///     }
+///
+///     fn free(&self, _ptr: *mut u8, _layout: Layout) {}
+/// }
+/// ```
+pub trait AllocatorTrait: Send + Sync {
 
     fn new() -> Allocator
     where Self: Sized {
-        return Allocator { inner: Arc::new(Self::new_impl()) }
+        return Allocator::from_boxed(Self::new_impl());
     }
 
     fn new_impl() -> Box<dyn AllocatorTrait>
@@ -150,4 +404,65 @@ pub trait AllocatorTrait {
     }
 
     fn free(&self, ptr: *mut u8, layout: Layout);
-}
\ No newline at end of file
+
+    /// Name of the allocator, used in errors and reports. Defaults to the type name.
+    fn name(&self) -> &'static str {
+        let type_name: &'static str = std::any::type_name::<Self>();
+        return type_name.rsplit("::").next().unwrap_or(type_name);
+    }
+
+    /// Checks if `ptr` points into memory handed out by this allocator.
+    /// Used by composite allocators to route a free to the allocator that owns the memory.
+    /// Allocators that cannot tell, such as general purpose heap allocators, return false.
+    fn owns(&self, _ptr: *const u8) -> bool {
+        return false;
+    }
+
+    /// Tries to grow the allocation at `ptr`, made with `layout`, to `new_size` bytes without moving it.
+    /// On success, the memory must afterwards be freed with a layout of `new_size` bytes.
+    /// Returns false if the allocator cannot grow in place, which is the default.
+    fn grow_in_place(&self, _ptr: *mut u8, _layout: Layout, _new_size: usize) -> bool {
+        return false;
+    }
+
+    /// Tries to shrink the allocation at `ptr`, made with `layout`, to `new_size` bytes without moving it.
+    /// On success, the memory must afterwards be freed with a layout of `new_size` bytes.
+    /// Returns false if the allocator cannot shrink in place, which is the default.
+    fn shrink_in_place(&self, _ptr: *mut u8, _layout: Layout, _new_size: usize) -> bool {
+        return false;
+    }
+
+    /// Current usage of the allocator, or None if it does not keep track, which is the default.
+    fn stats(&self) -> Option<AllocatorStats> {
+        return None;
+    }
+
+    /// Total bytes the allocator can hand out, or None if it is only limited by its parent or the system, which is the default.
+    fn capacity(&self) -> Option<usize> {
+        return None;
+    }
+
+    /// Bytes actually usable at `ptr`, which was allocated with `layout`. May be more than `layout.size()`
+    /// when the allocator rounds requests up, such as to a size class or page. The memory may afterwards be
+    /// freed or resized with a layout of any size from `layout.size()` up to the usable size.
+    /// Defaults to `layout.size()`.
+    fn usable_size(&self, _ptr: *const u8, layout: Layout) -> usize {
+        return layout.size();
+    }
+}
+
+/// Usage of an allocator as reported by `AllocatorTrait::stats()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AllocatorStats {
+    /// Bytes currently allocated.
+    pub used_bytes: usize,
+    /// Highest amount of bytes that were allocated at once.
+    pub peak_bytes: usize,
+    /// Number of allocations currently alive, if the allocator counts them.
+    pub live_allocations: Option<usize>
+}
+
+/// Weak reference to every allocator created through `Allocator::from_boxed()`, for `Allocator::live_allocators()`.
+struct RegisteredAllocator(Weak<Box<dyn AllocatorTrait>>);
+
+static REGISTERED_ALLOCATORS: Mutex<Vec<RegisteredAllocator>> = Mutex::new(Vec::new());
\ No newline at end of file
@@ -0,0 +1,113 @@
+use super::{allocator::{AllocatorTrait, AllocErr, Allocator, AllocatorStats}, heap_allocator::global_heap_allocator};
+use std::{alloc::Layout, sync::atomic::{AtomicUsize, Ordering}};
+
+const DEFAULT_ARENA_CAPACITY: usize = 64 * 1024;
+const ARENA_BUFFER_ALIGNMENT: usize = 64;
+
+/// Bump allocator over a fixed size buffer that is allocated from a parent allocator up front.
+/// Allocation is a lock-free bump of an offset, and the memory is returned to the parent when the arena is dropped.
+/// Freeing only reclaims memory if it was the most recent allocation, otherwise it is a no-op.
+/// Fails with `AllocErr` once the buffer is used up, which makes it a good primary for `FallbackAllocator`.
+/// ```
+/// # use gk_types_rs::allocator::arena_allocator::ArenaAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let arena = ArenaAllocator::new_arena(global_heap_allocator(), 256);
+/// let a = arena.malloc_buffer::<u64>(24).unwrap();
+/// assert!(arena.owns(a as *const u8));
+/// // The arena is used up.
+/// assert!(arena.malloc_buffer::<u64>(24).is_err());
+/// arena.free_buffer(a, 24);
+/// ```
+pub struct ArenaAllocator {
+    parent: Allocator,
+    buffer: *mut u8,
+    capacity: usize,
+    offset: AtomicUsize,
+    peak: AtomicUsize
+}
+
+// The arena owns `buffer`, which is never moved, and every allocation from it claims a disjoint range through the
+// atomic offset.
+unsafe impl Send for ArenaAllocator {}
+unsafe impl Sync for ArenaAllocator {}
+
+impl ArenaAllocator {
+    /// Creates an arena with `capacity` bytes allocated from `parent`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` cannot allocate the arena buffer.
+    pub fn new_arena(parent: &Allocator, capacity: usize) -> Allocator {
+        return Allocator::from_boxed(Box::new(Self::new_with_parent(parent, capacity)));
+    }
+
+    fn new_with_parent(parent: &Allocator, capacity: usize) -> Self {
+        let buffer = parent.malloc_aligned_buffer::<u8>(capacity, ARENA_BUFFER_ALIGNMENT).unwrap();
+        return ArenaAllocator {
+            parent: parent.clone(),
+            buffer,
+            capacity,
+            offset: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0)
+        }
+    }
+}
+
+impl AllocatorTrait for ArenaAllocator {
+    fn new_impl() -> Box<dyn AllocatorTrait>
+    where Self: Sized {
+        return Box::new(Self::new_with_parent(global_heap_allocator(), DEFAULT_ARENA_CAPACITY));
+    }
+
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        let base = self.buffer as usize;
+        let mut current = self.offset.load(Ordering::Acquire);
+        loop {
+            let align_mask = layout.align() - 1;
+            let start = ((base + current + align_mask) & !align_mask) - base;
+            let end = match start.checked_add(layout.size()) {
+                Some(end) if end <= self.capacity => end,
+                _ => return Err(AllocErr::Exhausted { layout, allocator: self.name() })
+            };
+            match self.offset.compare_exchange_weak(current, end, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => {
+                    self.peak.fetch_max(end, Ordering::AcqRel);
+                    return Ok(unsafe { self.buffer.add(start) });
+                },
+                Err(actual) => current = actual
+            }
+        }
+    }
+
+    fn free(&self, ptr: *mut u8, layout: Layout) {
+        debug_assert!(self.owns(ptr), "Freeing memory that was not allocated by this arena");
+        // Only the most recent allocation can be given back.
+        let start = ptr as usize - self.buffer as usize;
+        let _ = self.offset.compare_exchange(start + layout.size(), start, Ordering::AcqRel, Ordering::Relaxed);
+    }
+
+    fn owns(&self, ptr: *const u8) -> bool {
+        let address = ptr as usize;
+        let base = self.buffer as usize;
+        return address >= base && address < base + self.capacity;
+    }
+
+    /// Used bytes include padding for alignment and memory lost to frees that could not be reclaimed.
+    fn stats(&self) -> Option<AllocatorStats> {
+        return Some(AllocatorStats {
+            used_bytes: self.offset.load(Ordering::Acquire),
+            peak_bytes: self.peak.load(Ordering::Acquire),
+            live_allocations: None
+        });
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        return Some(self.capacity);
+    }
+}
+
+impl Drop for ArenaAllocator {
+    fn drop(&mut self) {
+        self.parent.free_aligned_buffer(self.buffer, self.capacity, ARENA_BUFFER_ALIGNMENT);
+    }
+}
@@ -0,0 +1,276 @@
+use super::{allocator::{AllocatorTrait, AllocErr, Allocator, AllocatorStats}, heap_allocator::global_heap_allocator};
+use std::{alloc::Layout, sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}}};
+
+/// Information passed to a budget's over-budget callback when an allocation is refused.
+#[derive(Debug, Clone, Copy)]
+pub struct OverBudget {
+    /// Name of the budget that refused the allocation.
+    pub name: &'static str,
+    /// Layout of the refused allocation.
+    pub layout: Layout,
+    /// Bytes that were in use when the allocation was attempted.
+    pub used: usize,
+    /// Byte limit of the budget.
+    pub limit: usize
+}
+
+type OverBudgetCallback = Arc<dyn Fn(&OverBudget) + Send + Sync>;
+
+/// Shared accounting state of a `BudgetAllocator`. Can be queried while the allocator is in use,
+/// and the limit can be changed at runtime.
+pub struct Budget {
+    name: &'static str,
+    limit: AtomicUsize,
+    used: AtomicUsize,
+    peak: AtomicUsize,
+    on_over_budget: Mutex<Option<OverBudgetCallback>>
+}
+
+impl Budget {
+    fn new(name: &'static str, limit: usize) -> Self {
+        return Budget {
+            name,
+            limit: AtomicUsize::new(limit),
+            used: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            on_over_budget: Mutex::new(None)
+        }
+    }
+
+    /// Name given to the budget on creation.
+    pub fn name(&self) -> &'static str {
+        return self.name;
+    }
+
+    /// Maximum amount of bytes that may be allocated through the budget at once.
+    pub fn limit(&self) -> usize {
+        return self.limit.load(Ordering::Acquire);
+    }
+
+    /// Changes the byte limit. Lowering the limit below `used()` does not free anything,
+    /// but all further allocations will be refused until enough memory is freed.
+    pub fn set_limit(&self, new_limit: usize) {
+        self.limit.store(new_limit, Ordering::Release);
+    }
+
+    /// Bytes currently allocated through the budget.
+    pub fn used(&self) -> usize {
+        return self.used.load(Ordering::Acquire);
+    }
+
+    /// Bytes that can still be allocated before hitting the limit.
+    pub fn remaining(&self) -> usize {
+        return self.limit().saturating_sub(self.used());
+    }
+
+    /// Highest amount of bytes that were allocated through the budget at once.
+    pub fn peak(&self) -> usize {
+        return self.peak.load(Ordering::Acquire);
+    }
+
+    /// Sets the function that is called whenever an allocation would exceed the limit.
+    /// The allocation is refused after the callback returns.
+    /// ```
+    /// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// # use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    /// let (allocator, budget) = BudgetAllocator::new_budget("audio", global_heap_allocator(), 64);
+    /// let refused = Arc::new(AtomicUsize::new(0));
+    /// let refused_clone = refused.clone();
+    /// budget.set_over_budget_callback(move |info| {
+    ///     assert_eq!(info.name, "audio");
+    ///     refused_clone.fetch_add(1, Ordering::Relaxed);
+    /// });
+    /// assert!(allocator.malloc_buffer::<u8>(128).is_err());
+    /// assert_eq!(refused.load(Ordering::Relaxed), 1);
+    /// ```
+    /// Failing to grow an allocation in place is not reported, since the caller can still fall back to a new allocation.
+    /// ```
+    /// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// # use std::{alloc::Layout, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+    /// let (allocator, budget) = BudgetAllocator::new_budget("audio", global_heap_allocator(), 64);
+    /// let refused = Arc::new(AtomicUsize::new(0));
+    /// let refused_clone = refused.clone();
+    /// budget.set_over_budget_callback(move |_| { refused_clone.fetch_add(1, Ordering::Relaxed); });
+    /// let layout = Layout::array::<u8>(48).unwrap();
+    /// let samples = allocator.malloc(layout).unwrap();
+    /// assert!(!allocator.grow_in_place(samples, layout, 96));
+    /// assert_eq!(refused.load(Ordering::Relaxed), 0);
+    /// assert_eq!((budget.used(), budget.peak()), (48, 48));
+    /// allocator.free(samples, layout);
+    /// ```
+    pub fn set_over_budget_callback<F>(&self, callback: F)
+    where F: Fn(&OverBudget) + Send + Sync + 'static {
+        *self.on_over_budget.lock().unwrap() = Some(Arc::new(callback));
+    }
+
+    /// Removes the over-budget callback, if one is set.
+    pub fn clear_over_budget_callback(&self) {
+        *self.on_over_budget.lock().unwrap() = None;
+    }
+
+    /// Reserves `layout` for an allocation, reporting it through the over-budget callback if it does not fit.
+    fn try_reserve(&self, layout: Layout) -> Result<(), AllocErr> {
+        return match self.reserve(layout.size()) {
+            Ok(new_used) => {
+                self.peak.fetch_max(new_used, Ordering::AcqRel);
+                Ok(())
+            },
+            Err((used, limit)) => {
+                self.notify_over_budget(layout, used, limit);
+                Err(AllocErr::BudgetExceeded { layout, allocator: self.name, used, limit })
+            }
+        };
+    }
+
+    /// Adds `size` to the used bytes if it fits, returning the new amount used, or the amount used and the limit.
+    /// Does not report anything or update the peak, so it can be used for attempts that may be undone.
+    fn reserve(&self, size: usize) -> Result<usize, (usize, usize)> {
+        let mut current = self.used.load(Ordering::Acquire);
+        loop {
+            let limit = self.limit();
+            let new_used = match current.checked_add(size) {
+                Some(new_used) if new_used <= limit => new_used,
+                _ => return Err((current, limit))
+            };
+            match self.used.compare_exchange_weak(current, new_used, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return Ok(new_used),
+                Err(actual) => current = actual
+            }
+        }
+    }
+
+    fn release(&self, size: usize) {
+        self.used.fetch_sub(size, Ordering::AcqRel);
+    }
+
+    fn notify_over_budget(&self, layout: Layout, used: usize, limit: usize) {
+        // Clone out of the lock so the callback may freely change the callback or limit.
+        let callback = self.on_over_budget.lock().unwrap().clone();
+        if let Some(callback) = callback {
+            callback(&OverBudget { name: self.name, layout, used, limit });
+        }
+    }
+}
+
+/// Enforces a byte limit on top of a parent allocator. Allocations that would exceed the limit are refused
+/// with an error, and reported through the budget's over-budget callback.
+///
+/// Budgets nest by using another budget's allocator as the parent.
+/// An allocation through a nested budget counts against every budget above it.
+/// ```
+/// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let (level, level_budget) = BudgetAllocator::new_budget("level", global_heap_allocator(), 1024);
+/// let (physics, physics_budget) = BudgetAllocator::new_budget("physics", &level, 256);
+///
+/// let buffer = physics.malloc_buffer::<u8>(200).unwrap();
+/// assert_eq!(physics_budget.used(), 200);
+/// assert_eq!(level_budget.used(), 200);
+/// // Would exceed the physics budget, even though the level budget has room.
+/// assert!(physics.malloc_buffer::<u8>(100).is_err());
+///
+/// physics.free_buffer(buffer, 200);
+/// assert_eq!(physics_budget.used(), 0);
+/// assert_eq!(level_budget.used(), 0);
+/// ```
+pub struct BudgetAllocator {
+    parent: Allocator,
+    budget: Arc<Budget>
+}
+
+impl BudgetAllocator {
+    /// Creates a budgeted allocator that allocates through `parent`, along with the shared budget
+    /// used to query and adjust it.
+    /// ```
+    /// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// # use gk_types_rs::array::array_list::ArrayList;
+    /// let (allocator, budget) = BudgetAllocator::new_budget("ai", global_heap_allocator(), 4096);
+    /// let mut array_list: ArrayList<String> = ArrayList::new(&allocator);
+    /// array_list.push(String::from("hello world!"));
+    /// assert!(budget.used() > 0);
+    /// assert_eq!(budget.limit(), 4096);
+    /// ```
+    pub fn new_budget(name: &'static str, parent: &Allocator, limit: usize) -> (Allocator, Arc<Budget>) {
+        let budget = Arc::new(Budget::new(name, limit));
+        let allocator = Allocator::from_boxed(Box::new(BudgetAllocator {
+            parent: parent.clone(),
+            budget: budget.clone()
+        }));
+        return (allocator, budget);
+    }
+}
+
+impl AllocatorTrait for BudgetAllocator {
+    /// Unlimited budget on top of the global heap allocator. Prefer `BudgetAllocator::new_budget()`.
+    fn new_impl() -> Box<dyn AllocatorTrait>
+    where Self: Sized {
+        return Box::new(BudgetAllocator {
+            parent: global_heap_allocator().clone(),
+            budget: Arc::new(Budget::new("unlimited", usize::MAX))
+        });
+    }
+
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        self.budget.try_reserve(layout)?;
+        let result = self.parent.malloc(layout);
+        if result.is_err() {
+            self.budget.release(layout.size());
+        }
+        return result;
+    }
+
+    fn malloc_zero(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        self.budget.try_reserve(layout)?;
+        let result = self.parent.malloc_zero(layout);
+        if result.is_err() {
+            self.budget.release(layout.size());
+        }
+        return result;
+    }
+
+    fn free(&self, ptr: *mut u8, layout: Layout) {
+        self.parent.free(ptr, layout);
+        self.budget.release(layout.size());
+    }
+
+    fn owns(&self, ptr: *const u8) -> bool {
+        return self.parent.owns(ptr);
+    }
+
+    fn grow_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        // Only an attempt, the caller falls back to a new allocation, which reports going over budget if it does.
+        let additional = new_size - layout.size();
+        let Ok(new_used) = self.budget.reserve(additional) else {
+            return false;
+        };
+        if !self.parent.grow_in_place(ptr, layout, new_size) {
+            self.budget.release(additional);
+            return false;
+        }
+        self.budget.peak.fetch_max(new_used, Ordering::AcqRel);
+        return true;
+    }
+
+    fn shrink_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        if !self.parent.shrink_in_place(ptr, layout, new_size) {
+            return false;
+        }
+        self.budget.release(layout.size() - new_size);
+        return true;
+    }
+
+    fn name(&self) -> &'static str {
+        return self.budget.name();
+    }
+
+    fn stats(&self) -> Option<AllocatorStats> {
+        return Some(AllocatorStats { used_bytes: self.budget.used(), peak_bytes: self.budget.peak(), live_allocations: None });
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        return Some(self.budget.limit());
+    }
+}
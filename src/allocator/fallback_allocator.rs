@@ -0,0 +1,88 @@
+use super::{allocator::{AllocatorTrait, AllocErr, Allocator}, heap_allocator::global_heap_allocator};
+use std::alloc::Layout;
+
+/// Tries to allocate from a primary allocator, and falls back to a secondary allocator if the primary fails.
+/// Frees are routed back to the primary if it `owns()` the pointer, otherwise to the secondary,
+/// so the primary MUST be able to report ownership (such as `ArenaAllocator`).
+/// ```
+/// # use gk_types_rs::allocator::fallback_allocator::FallbackAllocator;
+/// # use gk_types_rs::allocator::arena_allocator::ArenaAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let arena = ArenaAllocator::new_arena(global_heap_allocator(), 64);
+/// let allocator = FallbackAllocator::new_fallback(&arena, global_heap_allocator());
+///
+/// let small = allocator.malloc_buffer::<u8>(32).unwrap();
+/// assert!(arena.owns(small));
+/// // Does not fit in the arena anymore, so it comes from the heap.
+/// let large = allocator.malloc_buffer::<u8>(128).unwrap();
+/// assert!(!arena.owns(large));
+///
+/// allocator.free_buffer(large, 128);
+/// allocator.free_buffer(small, 32);
+/// ```
+pub struct FallbackAllocator {
+    primary: Allocator,
+    secondary: Allocator
+}
+
+impl FallbackAllocator {
+    /// Creates an allocator that uses `primary` first, and `secondary` when `primary` returns an error.
+    pub fn new_fallback(primary: &Allocator, secondary: &Allocator) -> Allocator {
+        return Allocator::from_boxed(Box::new(FallbackAllocator {
+            primary: primary.clone(),
+            secondary: secondary.clone()
+        }));
+    }
+
+    fn owner_of(&self, ptr: *const u8) -> &Allocator {
+        if self.primary.owns(ptr) {
+            return &self.primary;
+        }
+        return &self.secondary;
+    }
+}
+
+impl AllocatorTrait for FallbackAllocator {
+    /// Falls back from the global heap allocator onto itself, so prefer `FallbackAllocator::new_fallback()`.
+    fn new_impl() -> Box<dyn AllocatorTrait>
+    where Self: Sized {
+        return Box::new(FallbackAllocator {
+            primary: global_heap_allocator().clone(),
+            secondary: global_heap_allocator().clone()
+        });
+    }
+
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        return match self.primary.malloc(layout) {
+            Ok(ptr) => Ok(ptr),
+            Err(_) => self.secondary.malloc(layout)
+        };
+    }
+
+    fn malloc_zero(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        return match self.primary.malloc_zero(layout) {
+            Ok(ptr) => Ok(ptr),
+            Err(_) => self.secondary.malloc_zero(layout)
+        };
+    }
+
+    fn free(&self, ptr: *mut u8, layout: Layout) {
+        self.owner_of(ptr).free(ptr, layout);
+    }
+
+    fn owns(&self, ptr: *const u8) -> bool {
+        return self.primary.owns(ptr) || self.secondary.owns(ptr);
+    }
+
+    fn grow_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        return self.owner_of(ptr).grow_in_place(ptr, layout, new_size);
+    }
+
+    fn shrink_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        return self.owner_of(ptr).shrink_in_place(ptr, layout, new_size);
+    }
+
+    fn usable_size(&self, ptr: *const u8, layout: Layout) -> usize {
+        return self.owner_of(ptr).usable_size(ptr, layout);
+    }
+}
@@ -0,0 +1,211 @@
+use super::allocator::Allocator;
+use std::{alloc::{GlobalAlloc, Layout, System, AllocError, Allocator as StdAllocator}, cell::Cell, mem::{size_of, align_of}, ptr::NonNull, sync::atomic::{AtomicPtr, Ordering}};
+
+// Written in front of every allocation made through the bridge, so a dealloc knows where the memory came from.
+// Allocator tagged memory also records the installed handle that made it, right before the tag.
+const SYSTEM_ALLOCATION_TAG: usize = 0x5359_5354_454D_0001;
+const ALLOCATOR_ALLOCATION_TAG: usize = 0x4746_414C_4C4F_0002;
+
+thread_local! {
+    // Set while the installed allocator is running on this thread. Any allocation it makes internally
+    // (such as HeapAllocator calling std::alloc) comes back into the bridge, and must go to the system allocator.
+    static IS_IN_ALLOCATOR: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Allows any `Allocator` to be used as the process `#[global_allocator]`, so that everything using std
+/// collections goes through the same allocator as `ArrayList` does.
+///
+/// Until `install()` is called, and for any allocation made from within the installed allocator itself,
+/// memory comes from the system allocator. Every allocation carries a small header recording its origin,
+/// so memory is always returned to where it came from.
+/// ```
+/// # use gk_types_rs::allocator::global_bridge::GlobalAllocatorBridge;
+/// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// #[global_allocator]
+/// static GLOBAL: GlobalAllocatorBridge = GlobalAllocatorBridge::new();
+///
+/// fn main() {
+///     let (allocator, budget) = BudgetAllocator::new_budget("process", global_heap_allocator(), usize::MAX);
+///     GLOBAL.install(&allocator);
+///     let v: Vec<u8> = vec![0; 1024];
+///     assert!(budget.used() >= 1024);
+///     drop(v);
+/// }
+/// ```
+pub struct GlobalAllocatorBridge {
+    installed: AtomicPtr<Allocator>
+}
+
+impl GlobalAllocatorBridge {
+    /// Creates a bridge that uses the system allocator until `install()` is called.
+    pub const fn new() -> Self {
+        return GlobalAllocatorBridge { installed: AtomicPtr::new(std::ptr::null_mut()) }
+    }
+
+    /// Routes all further allocations through `allocator`. Memory allocated before the install is still
+    /// freed back to the system allocator.
+    ///
+    /// Installing again replaces the allocator for new allocations. Memory allocated before then is still freed
+    /// through the allocator that made it, so the previously installed handle is intentionally leaked.
+    /// ```
+    /// # use gk_types_rs::allocator::global_bridge::GlobalAllocatorBridge;
+    /// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// #[global_allocator]
+    /// static GLOBAL: GlobalAllocatorBridge = GlobalAllocatorBridge::new();
+    ///
+    /// fn main() {
+    ///     let (loading, loading_budget) = BudgetAllocator::new_budget("loading", global_heap_allocator(), usize::MAX);
+    ///     let (gameplay, gameplay_budget) = BudgetAllocator::new_budget("gameplay", global_heap_allocator(), usize::MAX);
+    ///     GLOBAL.install(&loading);
+    ///     let level: Vec<u8> = vec![0; 4096];
+    ///     GLOBAL.install(&gameplay);
+    ///     let used_by_gameplay = gameplay_budget.used();
+    ///     drop(level);
+    ///     // Returned to the allocator that made it.
+    ///     assert!(loading_budget.used() < 4096);
+    ///     assert_eq!(gameplay_budget.used(), used_by_gameplay);
+    /// }
+    /// ```
+    pub fn install(&self, allocator: &Allocator) {
+        let handle = Box::into_raw(Box::new(allocator.clone()));
+        self.installed.store(handle, Ordering::Release);
+    }
+
+    /// Checks if an allocator has been installed.
+    pub fn is_installed(&self) -> bool {
+        return !self.installed.load(Ordering::Acquire).is_null();
+    }
+
+    // Layout of the allocation including the origin header, and the offset from the start to the user memory.
+    // None if the header makes the allocation too large.
+    fn header_layout(layout: Layout) -> Option<(Layout, usize)> {
+        let align = layout.align().max(align_of::<usize>());
+        let offset = align.max(2 * size_of::<usize>());
+        let full_layout = Layout::from_size_align(layout.size().checked_add(offset)?, align).ok()?;
+        return Some((full_layout, offset));
+    }
+
+    fn enter_allocator() -> bool {
+        return IS_IN_ALLOCATOR.try_with(|is_in| {
+            if is_in.get() {
+                return false;
+            }
+            is_in.set(true);
+            return true;
+        }).unwrap_or(false);
+    }
+
+    fn exit_allocator() {
+        let _ = IS_IN_ALLOCATOR.try_with(|is_in| is_in.set(false));
+    }
+
+    unsafe fn alloc_with(&self, layout: Layout, zeroed: bool) -> *mut u8 {
+        let Some((full_layout, offset)) = Self::header_layout(layout) else {
+            return std::ptr::null_mut();
+        };
+        let installed = self.installed.load(Ordering::Acquire);
+
+        let (base, tag, owner) = if !installed.is_null() && Self::enter_allocator() {
+            let result = if zeroed { (*installed).malloc_zero(full_layout) } else { (*installed).malloc(full_layout) };
+            Self::exit_allocator();
+            (result.unwrap_or(std::ptr::null_mut()), ALLOCATOR_ALLOCATION_TAG, installed)
+        }
+        else if zeroed {
+            (System.alloc_zeroed(full_layout), SYSTEM_ALLOCATION_TAG, std::ptr::null_mut())
+        }
+        else {
+            (System.alloc(full_layout), SYSTEM_ALLOCATION_TAG, std::ptr::null_mut())
+        };
+
+        if base.is_null() {
+            return base;
+        }
+        let ptr = base.add(offset);
+        (ptr as *mut usize).sub(1).write(tag);
+        (ptr as *mut *mut Allocator).sub(2).write(owner);
+        return ptr;
+    }
+}
+
+impl Default for GlobalAllocatorBridge {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+unsafe impl GlobalAlloc for GlobalAllocatorBridge {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        return self.alloc_with(layout, false);
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        return self.alloc_with(layout, true);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // The layout was accepted when allocating, so the header still fits.
+        let (full_layout, offset) = Self::header_layout(layout).unwrap();
+        let tag = (ptr as *const usize).sub(1).read();
+        let base = ptr.sub(offset);
+        match tag {
+            SYSTEM_ALLOCATION_TAG => System.dealloc(base, full_layout),
+            ALLOCATOR_ALLOCATION_TAG => {
+                // Freed through the allocator that made it, even if another has been installed since.
+                let owner = (ptr as *const *const Allocator).sub(2).read();
+                debug_assert!(!owner.is_null());
+                // Any memory the allocator frees internally was allocated with the guard held, so is system tagged.
+                let was_in_allocator = !Self::enter_allocator();
+                (*owner).free(base, full_layout);
+                if !was_in_allocator {
+                    Self::exit_allocator();
+                }
+            },
+            _ => panic!("GlobalAllocatorBridge cannot free memory it did not allocate")
+        }
+    }
+}
+
+/// Lets std collections on nightly allocate through the same handle as `ArrayList`, such as `Vec::new_in()`.
+/// Zero sized allocations do not touch the underlying allocator.
+/// ```
+/// #![feature(allocator_api)]
+/// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let (allocator, budget) = BudgetAllocator::new_budget("tools", global_heap_allocator(), 1024);
+/// let mut v: Vec<u32, _> = Vec::with_capacity_in(16, allocator.clone());
+/// v.push(1);
+/// assert_eq!(budget.used(), 64);
+/// drop(v);
+/// assert_eq!(budget.used(), 0);
+/// ```
+unsafe impl StdAllocator for Allocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(NonNull::slice_from_raw_parts(Self::dangling(layout), 0));
+        }
+        let ptr = self.malloc(layout).map_err(|_| AllocError)?;
+        return NonNull::new(ptr).map(|ptr| NonNull::slice_from_raw_parts(ptr, layout.size())).ok_or(AllocError);
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(NonNull::slice_from_raw_parts(Self::dangling(layout), 0));
+        }
+        let ptr = self.malloc_zero(layout).map_err(|_| AllocError)?;
+        return NonNull::new(ptr).map(|ptr| NonNull::slice_from_raw_parts(ptr, layout.size())).ok_or(AllocError);
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.free(ptr.as_ptr(), layout);
+        }
+    }
+}
+
+impl Allocator {
+    fn dangling(layout: Layout) -> NonNull<u8> {
+        return unsafe { NonNull::new_unchecked(std::ptr::without_provenance_mut(layout.align())) };
+    }
+}
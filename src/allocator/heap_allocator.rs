@@ -9,11 +9,11 @@ impl AllocatorTrait for HeapAllocator {
         return Box::new(HeapAllocator{});
     }
 
-    fn malloc(&self, layout: Layout) -> Result<*mut u8, super::allocator::AllocErr> {
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
         unsafe {
             let ptr = alloc(layout);
             if ptr.is_null() {
-                return Err(AllocErr::OutOfMemory);
+                return Err(AllocErr::OutOfMemory { layout, allocator: self.name() });
             }
 
             return Ok(ptr);
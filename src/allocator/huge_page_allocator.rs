@@ -0,0 +1,340 @@
+use super::{allocator::{AllocatorTrait, AllocErr, Allocator}, virtual_memory::{page_size, round_to_page_size}};
+use std::{alloc::Layout, ptr::NonNull};
+
+/// Size of a default huge page on x86_64 and most aarch64 Linux systems.
+/// Used by `huge_page_size()` when the system does not report its own.
+pub const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// Size of the pages a `HugePageAllocator` using `mode` rounds allocations up to, and aligns them to.
+/// For `HugePageMode::Explicit`, this is the default size of the reserved huge page pool (`Hugepagesize` in `/proc/meminfo`).
+/// For `HugePageMode::Transparent`, it is the size of a transparent huge page (`/sys/kernel/mm/transparent_hugepage/hpage_pmd_size`).
+/// Falls back to `HUGE_PAGE_SIZE` where Linux does not report it. Is the normal page size for `HugePageMode::Disabled`,
+/// and on systems other than Linux, where huge pages are not used.
+/// ```
+/// # use gk_types_rs::allocator::huge_page_allocator::{huge_page_size, HugePageMode};
+/// # use gk_types_rs::allocator::virtual_memory::page_size;
+/// assert!(huge_page_size(HugePageMode::Transparent).is_power_of_two());
+/// assert_eq!(huge_page_size(HugePageMode::Disabled), page_size());
+/// ```
+pub fn huge_page_size(mode: HugePageMode) -> usize {
+    if mode == HugePageMode::Disabled {
+        return page_size();
+    }
+    let size = platform::huge_page_size(mode).filter(|size| size.is_power_of_two()).unwrap_or(HUGE_PAGE_SIZE);
+    return size.max(page_size());
+}
+
+/// How a `HugePageAllocator` asks the system for huge pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugePageMode {
+    /// Uses pages from the reserved huge page pool (`MAP_HUGETLB`). Falls back to `Transparent` if the pool is empty or unavailable.
+    Explicit,
+    /// Uses normal pages, and advises the kernel to back them with transparent huge pages (`MADV_HUGEPAGE`).
+    Transparent,
+    /// Uses normal pages only.
+    Disabled
+}
+
+/// Which NUMA node a `HugePageAllocator` places memory on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumaPolicy {
+    /// Leaves placement to the system.
+    Any,
+    /// Prefers the given node, but uses other nodes when it is out of memory.
+    Preferred(usize),
+    /// Strictly binds the memory to the given node. Unlike the other policies, allocating fails with
+    /// `AllocErr::OutOfMemory` if the memory cannot be bound, such as on a system without NUMA support.
+    Bind(usize),
+    /// Prefers the node of the CPU that the allocating thread is running on. Useful for threads pinned to a node.
+    Local
+}
+
+/// Maps allocations directly from the system, optionally backed by huge pages and placed on a specific NUMA node.
+/// Meant for large, long lived buffers, such as big `ArrayList`s of simulation data where TLB misses are costly.
+/// Every allocation is rounded up to, and aligned to, the page size, or `huge_page_size()` when huge pages are requested,
+/// since the kernel only backs whole aligned huge pages with transparent huge pages.
+///
+/// Huge pages and NUMA placement other than `NumaPolicy::Bind` are best effort. If the system does not support them,
+/// isn't configured for them, or isn't Linux, the allocation still succeeds with normal pages and default placement.
+/// Allocations aligned to more than the page size fail.
+/// ```
+/// # use gk_types_rs::allocator::huge_page_allocator::{HugePageAllocator, HugePageMode, NumaPolicy};
+/// # use gk_types_rs::array::array_list::ArrayList;
+/// let allocator = HugePageAllocator::new_huge_page(HugePageMode::Explicit, NumaPolicy::Local);
+/// let mut array_list: ArrayList<f32> = ArrayList::with_capacity(&allocator, 1024 * 1024);
+/// for i in 0..1024 * 1024 {
+///     array_list.push(i as f32);
+/// }
+/// assert_eq!(array_list[1000], 1000.0);
+/// ```
+/// Mappings start on a huge page boundary, and binding to a node that does not exist fails.
+/// ```
+/// # use gk_types_rs::allocator::huge_page_allocator::{HugePageAllocator, HugePageMode, NumaPolicy, huge_page_size, numa_node_count};
+/// # use std::alloc::Layout;
+/// let allocator = HugePageAllocator::new_huge_page(HugePageMode::Transparent, NumaPolicy::Any);
+/// let layout = Layout::from_size_align(100, 8).unwrap();
+/// let ptr = allocator.malloc(layout).unwrap();
+/// assert_eq!(ptr as usize % huge_page_size(HugePageMode::Transparent), 0);
+/// allocator.free(ptr, layout);
+///
+/// let missing_node = HugePageAllocator::new_huge_page(HugePageMode::Transparent, NumaPolicy::Bind(numa_node_count()));
+/// assert!(missing_node.malloc(layout).is_err());
+/// ```
+pub struct HugePageAllocator {
+    mode: HugePageMode,
+    numa_policy: NumaPolicy,
+    // Read once on creation, so that freeing always rounds the same way as allocating did.
+    huge_page_size: usize,
+    // Page size used when no explicit huge pages are available, which is usually much smaller than an explicit one.
+    fallback_page_size: usize
+}
+
+impl HugePageAllocator {
+    /// Creates an allocator that uses huge pages according to `mode`, and places memory according to `numa_policy`.
+    pub fn new_huge_page(mode: HugePageMode, numa_policy: NumaPolicy) -> Allocator {
+        return Allocator::from_boxed(Box::new(HugePageAllocator::with_mode(mode, numa_policy)));
+    }
+
+    fn with_mode(mode: HugePageMode, numa_policy: NumaPolicy) -> HugePageAllocator {
+        let fallback_mode = if mode == HugePageMode::Explicit { HugePageMode::Transparent } else { mode };
+        return HugePageAllocator { mode, numa_policy, huge_page_size: huge_page_size(mode), fallback_page_size: huge_page_size(fallback_mode) };
+    }
+
+    fn mapping_size(&self, layout: Layout) -> Result<usize, AllocErr> {
+        let size = layout.size().max(1);
+        return match self.mode {
+            HugePageMode::Disabled => round_to_page_size(size),
+            _ => size.checked_add(self.huge_page_size - 1).map(|padded| padded & !(self.huge_page_size - 1))
+                .ok_or(AllocErr::InvalidLayout { size, align: self.huge_page_size, allocator: self.name() })
+        };
+    }
+
+    // How much of a mapping is usable when it had to fall back to smaller pages, so that falling back from explicit
+    // huge pages does not commit a whole explicit huge page for a small allocation.
+    fn fallback_size(&self, layout: Layout, mapping_size: usize) -> usize {
+        return layout.size().max(1).checked_next_multiple_of(self.fallback_page_size).map_or(mapping_size, |size| size.min(mapping_size));
+    }
+}
+
+impl AllocatorTrait for HugePageAllocator {
+    fn new_impl() -> Box<dyn AllocatorTrait>
+    where Self: Sized {
+        return Box::new(HugePageAllocator::with_mode(HugePageMode::Transparent, NumaPolicy::Any));
+    }
+
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        if layout.align() > page_size() {
+            return Err(AllocErr::AlignmentUnsupported { layout, allocator: self.name() });
+        }
+        let mapping_size = self.mapping_size(layout)?;
+        let fallback_size = self.fallback_size(layout, mapping_size);
+        let ptr = unsafe { platform::map(mapping_size, fallback_size, self.fallback_page_size, self.mode, self.numa_policy) };
+        if ptr.is_null() {
+            return Err(AllocErr::OutOfMemory { layout, allocator: self.name() });
+        }
+        return Ok(ptr);
+    }
+
+    /// Freshly mapped pages are always zeroed, so no extra work is needed.
+    fn malloc_zero(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        return self.malloc(layout);
+    }
+
+    fn free(&self, ptr: *mut u8, layout: Layout) {
+        let Some(ptr) = NonNull::new(ptr) else {
+            return;
+        };
+        // Was rounded when allocating, so it rounds the same way again. Mappings that fell back to smaller pages
+        // still span the full size, so it does not matter which kind of pages backed it.
+        unsafe { platform::unmap(ptr, self.mapping_size(layout).unwrap()) };
+    }
+
+    /// Only counts what is usable regardless of whether explicit huge pages were available.
+    fn usable_size(&self, _ptr: *const u8, layout: Layout) -> usize {
+        return self.mapping_size(layout).map_or(layout.size(), |mapping_size| self.fallback_size(layout, mapping_size));
+    }
+}
+
+/// NUMA node of the CPU that the calling thread is currently running on, or None if it cannot be determined.
+/// ```
+/// # use gk_types_rs::allocator::huge_page_allocator::{current_numa_node, numa_node_count};
+/// if let Some(node) = current_numa_node() {
+///     assert!(node < numa_node_count());
+/// }
+/// ```
+pub fn current_numa_node() -> Option<usize> {
+    return platform::current_numa_node();
+}
+
+/// Number of NUMA nodes on the system. Is 1 on systems without NUMA, or where it cannot be determined.
+pub fn numa_node_count() -> usize {
+    return platform::numa_node_count();
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{HugePageMode, NumaPolicy};
+    use std::ptr::NonNull;
+    use crate::allocator::virtual_memory::page_size;
+
+    const MAX_NUMA_NODES: usize = 1024;
+
+    /// `size` must be a multiple of the page size, and `fallback_page_size` must be a power of two of at least the page size.
+    /// Only the first `fallback_size` bytes are usable if explicit huge pages are unavailable.
+    pub(super) unsafe fn map(size: usize, fallback_size: usize, fallback_page_size: usize, mode: HugePageMode, numa_policy: NumaPolicy) -> *mut u8 {
+        let protection = libc::PROT_READ | libc::PROT_WRITE;
+        let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+
+        let mut ptr = libc::MAP_FAILED;
+        if mode == HugePageMode::Explicit {
+            // Always aligned to the huge page size by the kernel.
+            ptr = libc::mmap(std::ptr::null_mut(), size, protection, flags | libc::MAP_HUGETLB, -1, 0);
+        }
+        if ptr == libc::MAP_FAILED {
+            let alignment = if mode == HugePageMode::Disabled { page_size() } else { fallback_page_size };
+            if fallback_size < size {
+                // The rest is only reserved, so that it is never committed but is still unmapped with the rest.
+                ptr = map_aligned(size, alignment, libc::PROT_NONE, flags | libc::MAP_NORESERVE);
+                if ptr != libc::MAP_FAILED && libc::mprotect(ptr, fallback_size, protection) != 0 {
+                    libc::munmap(ptr, size);
+                    ptr = libc::MAP_FAILED;
+                }
+            } else {
+                ptr = map_aligned(size, alignment, protection, flags);
+            }
+            if ptr == libc::MAP_FAILED {
+                return std::ptr::null_mut();
+            }
+            if mode != HugePageMode::Disabled {
+                // Fails harmlessly if transparent huge pages are disabled.
+                libc::madvise(ptr, size, libc::MADV_HUGEPAGE);
+            }
+        }
+
+        let node_and_mode = match numa_policy {
+            NumaPolicy::Any => None,
+            NumaPolicy::Preferred(node) => Some((node, libc::MPOL_PREFERRED)),
+            NumaPolicy::Bind(node) => Some((node, libc::MPOL_BIND)),
+            NumaPolicy::Local => current_numa_node().map(|node| (node, libc::MPOL_PREFERRED))
+        };
+        if let Some((node, policy)) = node_and_mode {
+            if node < MAX_NUMA_NODES {
+                let mut node_mask = [0 as libc::c_ulong; MAX_NUMA_NODES / libc::c_ulong::BITS as usize];
+                node_mask[node / libc::c_ulong::BITS as usize] |= 1 << (node % libc::c_ulong::BITS as usize);
+                // The pages are not touched yet, so they will be faulted in on the requested node.
+                let result = libc::syscall(libc::SYS_mbind, ptr, size, policy, node_mask.as_ptr(), MAX_NUMA_NODES + 1, 0);
+                // Only binding is strict. Other policies fail harmlessly on kernels without NUMA support.
+                if result != 0 && policy == libc::MPOL_BIND {
+                    libc::munmap(ptr, size);
+                    return std::ptr::null_mut();
+                }
+            } else if policy == libc::MPOL_BIND {
+                libc::munmap(ptr, size);
+                return std::ptr::null_mut();
+            }
+        }
+        return ptr as *mut u8;
+    }
+
+    // Maps `size` bytes starting at a multiple of `alignment`, by mapping an alignment's worth more,
+    // and unmapping what is left over before and after the aligned range.
+    unsafe fn map_aligned(size: usize, alignment: usize, protection: libc::c_int, flags: libc::c_int) -> *mut libc::c_void {
+        let Some(padded_size) = size.checked_add(alignment - page_size()) else {
+            return libc::MAP_FAILED;
+        };
+        let ptr = libc::mmap(std::ptr::null_mut(), padded_size, protection, flags, -1, 0);
+        if ptr == libc::MAP_FAILED || alignment == page_size() {
+            return ptr;
+        }
+        let head = (ptr as usize).next_multiple_of(alignment) - ptr as usize;
+        let aligned = (ptr as *mut u8).add(head);
+        if head > 0 {
+            libc::munmap(ptr, head);
+        }
+        let tail = padded_size - head - size;
+        if tail > 0 {
+            libc::munmap(aligned.add(size) as *mut libc::c_void, tail);
+        }
+        return aligned as *mut libc::c_void;
+    }
+
+    pub(super) fn huge_page_size(mode: HugePageMode) -> Option<usize> {
+        return match mode {
+            HugePageMode::Explicit => {
+                // Such as "Hugepagesize:       2048 kB".
+                let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+                let line = meminfo.lines().find(|line| line.starts_with("Hugepagesize:"))?;
+                let kilobytes = line.split_whitespace().nth(1)?.parse::<usize>().ok()?;
+                kilobytes.checked_mul(1024)
+            },
+            HugePageMode::Transparent => {
+                let size = std::fs::read_to_string("/sys/kernel/mm/transparent_hugepage/hpage_pmd_size").ok()?;
+                size.trim().parse::<usize>().ok()
+            },
+            HugePageMode::Disabled => None
+        };
+    }
+
+    pub(super) unsafe fn unmap(ptr: NonNull<u8>, size: usize) {
+        libc::munmap(ptr.as_ptr() as *mut libc::c_void, size);
+    }
+
+    pub(super) fn current_numa_node() -> Option<usize> {
+        let mut cpu: libc::c_uint = 0;
+        let mut node: libc::c_uint = 0;
+        let result = unsafe { libc::syscall(libc::SYS_getcpu, &mut cpu, &mut node, std::ptr::null_mut::<libc::c_void>()) };
+        if result != 0 {
+            return None;
+        }
+        return Some(node as usize);
+    }
+
+    pub(super) fn numa_node_count() -> usize {
+        // Formatted as ranges, such as "0" or "0-3" or "0,2-3".
+        let online = match std::fs::read_to_string("/sys/devices/system/node/online") {
+            Ok(online) => online,
+            Err(_) => return 1
+        };
+        let highest_node = online.trim().split([',', '-']).filter_map(|n| n.parse::<usize>().ok()).max();
+        return highest_node.map_or(1, |highest| highest + 1);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::{HugePageMode, NumaPolicy};
+    use std::ptr::NonNull;
+    use crate::allocator::virtual_memory::{reserve_pages, commit_pages, release_pages};
+
+    pub(super) unsafe fn map(size: usize, _fallback_size: usize, _fallback_page_size: usize, _mode: HugePageMode, numa_policy: NumaPolicy) -> *mut u8 {
+        // Placement cannot be controlled here, so strict binding always fails.
+        if let NumaPolicy::Bind(_) = numa_policy {
+            return std::ptr::null_mut();
+        }
+        let Some(ptr) = reserve_pages(size) else {
+            return std::ptr::null_mut();
+        };
+        if !commit_pages(ptr, size) {
+            release_pages(ptr, size);
+            return std::ptr::null_mut();
+        }
+        return ptr.as_ptr();
+    }
+
+    pub(super) unsafe fn unmap(ptr: NonNull<u8>, size: usize) {
+        release_pages(ptr, size);
+    }
+
+    pub(super) fn current_numa_node() -> Option<usize> {
+        return None;
+    }
+
+    pub(super) fn numa_node_count() -> usize {
+        return 1;
+    }
+
+    // Huge pages are not used here, so allocations are only rounded to normal pages.
+    pub(super) fn huge_page_size(_mode: HugePageMode) -> Option<usize> {
+        return Some(crate::allocator::virtual_memory::page_size());
+    }
+}
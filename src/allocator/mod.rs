@@ -1,2 +1,17 @@
 pub mod allocator;
-pub mod heap_allocator;
\ No newline at end of file
+pub mod heap_allocator;
+pub mod named_allocators;
+pub mod budget_allocator;
+pub mod arena_allocator;
+pub mod fallback_allocator;
+pub mod segregator_allocator;
+pub mod global_bridge;
+pub mod thread_cache_allocator;
+pub mod alloc_box;
+pub mod alloc_rc;
+pub mod alloc_arc;
+pub mod alloc_slice;
+pub mod virtual_memory;
+pub mod virtual_allocator;
+pub mod huge_page_allocator;
+pub mod tagging;
@@ -0,0 +1,106 @@
+use super::{allocator::Allocator, heap_allocator::global_heap_allocator};
+use std::{cell::RefCell, marker::PhantomData, sync::Mutex};
+
+static NAMED_ALLOCATORS: Mutex<Vec<(&'static str, Allocator)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static CURRENT_ALLOCATOR: RefCell<Option<Allocator>> = const { RefCell::new(None) };
+}
+
+/// Installs `allocator` globally under `name`, such as "frame", "persistent" or "debug", so any code can
+/// look it up with `named_allocator()` without having the handle passed to it. Usually done at startup.
+/// Returns the allocator that was previously installed under `name`, if any.
+/// ```
+/// # use gk_types_rs::allocator::named_allocators::{install_named_allocator, named_allocator};
+/// # use gk_types_rs::allocator::arena_allocator::ArenaAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let frame = ArenaAllocator::new_arena(global_heap_allocator(), 1024 * 1024);
+/// install_named_allocator("frame", &frame);
+///
+/// // Elsewhere, without access to `frame`.
+/// let allocator = named_allocator("frame").unwrap();
+/// assert!(allocator.ptr_eq(&frame));
+/// assert!(named_allocator("does not exist").is_none());
+/// ```
+pub fn install_named_allocator(name: &'static str, allocator: &Allocator) -> Option<Allocator> {
+    let mut named = NAMED_ALLOCATORS.lock().unwrap();
+    if let Some(entry) = named.iter_mut().find(|(entry_name, _)| *entry_name == name) {
+        return Some(std::mem::replace(&mut entry.1, allocator.clone()));
+    }
+    named.push((name, allocator.clone()));
+    return None;
+}
+
+/// Removes the allocator installed under `name`, returning it. Memory allocated from it stays valid
+/// for as long as a handle to it is held.
+pub fn uninstall_named_allocator(name: &str) -> Option<Allocator> {
+    let mut named = NAMED_ALLOCATORS.lock().unwrap();
+    let index = named.iter().position(|(entry_name, _)| *entry_name == name)?;
+    return Some(named.remove(index).1);
+}
+
+/// The allocator installed under `name`, if any. See `install_named_allocator()`.
+pub fn named_allocator(name: &str) -> Option<Allocator> {
+    let named = NAMED_ALLOCATORS.lock().unwrap();
+    return named.iter().find(|(entry_name, _)| *entry_name == name).map(|(_, allocator)| allocator.clone());
+}
+
+/// Every installed allocator along with its name, in installation order.
+pub fn named_allocators() -> Vec<(&'static str, Allocator)> {
+    return NAMED_ALLOCATORS.lock().unwrap().clone();
+}
+
+/// Makes `allocator` the current allocator of the calling thread until the returned scope is dropped.
+/// Containers created without an explicit allocator, such as through `ArrayList::default()`, use the current
+/// allocator, so a whole subsystem can be redirected without passing handles through it. Scopes nest.
+///
+/// The current allocator is not carried over into jobs, which run on other threads.
+/// ```
+/// # use gk_types_rs::allocator::named_allocators::{push_current_allocator, current_allocator};
+/// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// # use gk_types_rs::array::array_list::ArrayList;
+/// let (level, budget) = BudgetAllocator::new_budget("level", global_heap_allocator(), usize::MAX);
+/// {
+///     let _scope = push_current_allocator(&level);
+///     let mut array_list: ArrayList<u64> = ArrayList::default();
+///     for i in 0..100 {
+///         array_list.push(i);
+///     }
+///     assert!(budget.used() >= 800);
+/// }
+/// assert_eq!(budget.used(), 0);
+/// assert!(current_allocator().ptr_eq(global_heap_allocator()));
+/// ```
+pub fn push_current_allocator(allocator: &Allocator) -> CurrentAllocatorScope {
+    let previous = CURRENT_ALLOCATOR.with(|current| current.replace(Some(allocator.clone())));
+    return CurrentAllocatorScope { previous, _not_send: PhantomData };
+}
+
+/// The current allocator of the calling thread, or the global heap allocator if none is set.
+/// See `push_current_allocator()`.
+pub fn current_allocator() -> Allocator {
+    let current = CURRENT_ALLOCATOR.try_with(|current| current.borrow().clone()).ok().flatten();
+    return current.unwrap_or_else(|| global_heap_allocator().clone());
+}
+
+/// Restores the previous current allocator when dropped. See `push_current_allocator()`.
+/// Cannot be sent to another thread, since it restores the current allocator of the thread that pushed it.
+/// ``` compile_fail
+/// # use gk_types_rs::allocator::named_allocators::push_current_allocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let scope = push_current_allocator(global_heap_allocator());
+/// std::thread::spawn(move || drop(scope));
+/// ```
+#[must_use = "the allocator is only current until the scope is dropped"]
+pub struct CurrentAllocatorScope {
+    previous: Option<Allocator>,
+    _not_send: PhantomData<*const ()>
+}
+
+impl Drop for CurrentAllocatorScope {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        let _ = CURRENT_ALLOCATOR.try_with(|current| current.replace(previous));
+    }
+}
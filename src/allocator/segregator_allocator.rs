@@ -0,0 +1,114 @@
+use super::{allocator::{AllocatorTrait, AllocErr, Allocator}, heap_allocator::global_heap_allocator};
+use std::alloc::Layout;
+
+/// Routes allocations by size. Allocations of at most `threshold` bytes go to the small allocator,
+/// and everything larger goes to the large allocator. Frees are routed the same way using the layout size,
+/// so no ownership query is needed.
+/// ```
+/// # use gk_types_rs::allocator::segregator_allocator::SegregatorAllocator;
+/// # use gk_types_rs::allocator::arena_allocator::ArenaAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let arena = ArenaAllocator::new_arena(global_heap_allocator(), 1024);
+/// let allocator = SegregatorAllocator::new_segregator(64, &arena, global_heap_allocator());
+///
+/// let small = allocator.malloc_buffer::<u8>(64).unwrap();
+/// let large = allocator.malloc_buffer::<u8>(65).unwrap();
+/// assert!(arena.owns(small));
+/// assert!(!arena.owns(large));
+///
+/// allocator.free_buffer(large, 65);
+/// allocator.free_buffer(small, 64);
+/// ```
+/// Composes with other allocators, such as using a fallback for small allocations.
+/// ```
+/// # use gk_types_rs::allocator::segregator_allocator::SegregatorAllocator;
+/// # use gk_types_rs::allocator::fallback_allocator::FallbackAllocator;
+/// # use gk_types_rs::allocator::arena_allocator::ArenaAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// # use gk_types_rs::array::array_list::ArrayList;
+/// let arena = ArenaAllocator::new_arena(global_heap_allocator(), 4096);
+/// let small = FallbackAllocator::new_fallback(&arena, global_heap_allocator());
+/// let allocator = SegregatorAllocator::new_segregator(256, &small, global_heap_allocator());
+///
+/// let mut array_list: ArrayList<u64> = ArrayList::new(&allocator);
+/// for i in 0..1000 {
+///     array_list.push(i);
+/// }
+/// assert_eq!(array_list[999], 999);
+/// ```
+pub struct SegregatorAllocator {
+    threshold: usize,
+    small: Allocator,
+    large: Allocator
+}
+
+impl SegregatorAllocator {
+    /// Creates an allocator that sends allocations of at most `threshold` bytes to `small`, and the rest to `large`.
+    pub fn new_segregator(threshold: usize, small: &Allocator, large: &Allocator) -> Allocator {
+        return Allocator::from_boxed(Box::new(SegregatorAllocator {
+            threshold,
+            small: small.clone(),
+            large: large.clone()
+        }));
+    }
+
+    fn route(&self, layout: Layout) -> &Allocator {
+        if layout.size() <= self.threshold {
+            return &self.small;
+        }
+        return &self.large;
+    }
+}
+
+impl AllocatorTrait for SegregatorAllocator {
+    /// Routes everything to the global heap allocator, so prefer `SegregatorAllocator::new_segregator()`.
+    fn new_impl() -> Box<dyn AllocatorTrait>
+    where Self: Sized {
+        return Box::new(SegregatorAllocator {
+            threshold: 0,
+            small: global_heap_allocator().clone(),
+            large: global_heap_allocator().clone()
+        });
+    }
+
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        return self.route(layout).malloc(layout);
+    }
+
+    fn malloc_zero(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        return self.route(layout).malloc_zero(layout);
+    }
+
+    fn free(&self, ptr: *mut u8, layout: Layout) {
+        self.route(layout).free(ptr, layout);
+    }
+
+    fn owns(&self, ptr: *const u8) -> bool {
+        return self.small.owns(ptr) || self.large.owns(ptr);
+    }
+
+    /// Only succeeds if the new size is routed to the same allocator as the old one, so frees stay correctly routed.
+    fn grow_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        if (layout.size() <= self.threshold) != (new_size <= self.threshold) {
+            return false;
+        }
+        return self.route(layout).grow_in_place(ptr, layout, new_size);
+    }
+
+    /// Only succeeds if the new size is routed to the same allocator as the old one, so frees stay correctly routed.
+    fn shrink_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        if (layout.size() <= self.threshold) != (new_size <= self.threshold) {
+            return false;
+        }
+        return self.route(layout).shrink_in_place(ptr, layout, new_size);
+    }
+
+    /// Capped at the threshold for small allocations, so frees with the usable size stay correctly routed.
+    fn usable_size(&self, ptr: *const u8, layout: Layout) -> usize {
+        let usable_size = self.route(layout).usable_size(ptr, layout);
+        if layout.size() <= self.threshold {
+            return usable_size.min(self.threshold);
+        }
+        return usable_size;
+    }
+}
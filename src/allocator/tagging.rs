@@ -0,0 +1,404 @@
+use super::allocator::{AllocatorTrait, AllocErr, Allocator, AllocatorStats};
+use super::heap_allocator::global_heap_allocator;
+use std::{alloc::Layout, cell::Cell, collections::HashMap, fmt, marker::PhantomData, sync::{Arc, Mutex}};
+
+/// Tag used for allocations made without any tag set.
+pub const UNTAGGED: &str = "untagged";
+
+thread_local! {
+    static CURRENT_TAG: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// Sets the allocation tag of the calling thread until the returned scope is dropped.
+/// Every allocation made through a `TrackingAllocator` on this thread while the scope is alive is counted under `tag`,
+/// unless it goes through a `TaggedAllocator`, whose tag takes priority. Scopes nest.
+/// ```
+/// # use gk_types_rs::allocator::tagging::{TrackingAllocator, push_allocation_tag};
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let (allocator, tracker) = TrackingAllocator::new_tracking(global_heap_allocator());
+/// let ui = {
+///     let _scope = push_allocation_tag("ui");
+///     allocator.malloc_buffer::<u8>(100).unwrap()
+/// };
+/// assert_eq!(tracker.live_bytes("ui"), 100);
+/// allocator.free_buffer(ui, 100);
+/// assert_eq!(tracker.live_bytes("ui"), 0);
+/// ```
+pub fn push_allocation_tag(tag: &'static str) -> AllocationTagScope {
+    let previous = CURRENT_TAG.with(|current| current.replace(Some(tag)));
+    return AllocationTagScope { previous, _not_send: PhantomData };
+}
+
+/// The allocation tag of the calling thread, if one is set.
+pub fn current_allocation_tag() -> Option<&'static str> {
+    return CURRENT_TAG.try_with(|current| current.get()).ok().flatten();
+}
+
+/// Restores the previous allocation tag when dropped. See `push_allocation_tag()`.
+/// Cannot be sent to another thread, since it restores the tag of the thread that pushed it.
+/// ``` compile_fail
+/// # use gk_types_rs::allocator::tagging::push_allocation_tag;
+/// let scope = push_allocation_tag("audio");
+/// std::thread::spawn(move || drop(scope));
+/// ```
+#[must_use = "the tag is only set until the scope is dropped"]
+pub struct AllocationTagScope {
+    previous: Option<&'static str>,
+    _not_send: PhantomData<*const ()>
+}
+
+impl Drop for AllocationTagScope {
+    fn drop(&mut self) {
+        let _ = CURRENT_TAG.try_with(|current| current.set(self.previous));
+    }
+}
+
+/// Tags every allocation made through it, so a `TrackingAllocator` below it counts them under the tag.
+/// Since the tag belongs to the allocator handle, it follows anything holding the handle, such as an `ArrayList`.
+/// ```
+/// # use gk_types_rs::allocator::tagging::{TrackingAllocator, TaggedAllocator};
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// # use gk_types_rs::array::array_list::ArrayList;
+/// let (tracking, tracker) = TrackingAllocator::new_tracking(global_heap_allocator());
+/// let physics = TaggedAllocator::new_tagged("physics", &tracking);
+///
+/// let mut array_list: ArrayList<String> = ArrayList::with_capacity(&physics, 10);
+/// array_list.push(String::from("rigid body"));
+/// assert_eq!(tracker.live_bytes("physics"), 10 * std::mem::size_of::<String>());
+/// // Anything else allocated through the same handle is tagged too.
+/// let other = array_list.allocator().malloc_object::<u64>().unwrap();
+/// assert_eq!(tracker.live_count("physics"), 2);
+/// array_list.allocator().free_object(other);
+/// ```
+pub struct TaggedAllocator {
+    tag: &'static str,
+    parent: Allocator
+}
+
+impl TaggedAllocator {
+    /// Creates an allocator that tags every allocation made through `parent` with `tag`.
+    pub fn new_tagged(tag: &'static str, parent: &Allocator) -> Allocator {
+        return Allocator::from_boxed(Box::new(TaggedAllocator { tag, parent: parent.clone() }));
+    }
+}
+
+impl AllocatorTrait for TaggedAllocator {
+    fn new_impl() -> Box<dyn AllocatorTrait>
+    where Self: Sized {
+        return Box::new(TaggedAllocator { tag: UNTAGGED, parent: global_heap_allocator().clone() });
+    }
+
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        let _scope = push_allocation_tag(self.tag);
+        return self.parent.malloc(layout);
+    }
+
+    fn malloc_zero(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        let _scope = push_allocation_tag(self.tag);
+        return self.parent.malloc_zero(layout);
+    }
+
+    fn free(&self, ptr: *mut u8, layout: Layout) {
+        self.parent.free(ptr, layout);
+    }
+
+    fn owns(&self, ptr: *const u8) -> bool {
+        return self.parent.owns(ptr);
+    }
+
+    fn grow_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        return self.parent.grow_in_place(ptr, layout, new_size);
+    }
+
+    fn shrink_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        return self.parent.shrink_in_place(ptr, layout, new_size);
+    }
+
+    fn usable_size(&self, ptr: *const u8, layout: Layout) -> usize {
+        return self.parent.usable_size(ptr, layout);
+    }
+}
+
+impl Allocator {
+    /// Makes a handle that tags every allocation made through this allocator with `tag`. See `TaggedAllocator`.
+    /// ```
+    /// # use gk_types_rs::allocator::tagging::TrackingAllocator;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let (tracking, tracker) = TrackingAllocator::new_tracking(global_heap_allocator());
+    /// let ai = tracking.with_tag("ai");
+    /// let ptr = ai.malloc_object::<u32>().unwrap();
+    /// assert_eq!(tracker.live_bytes("ai"), 4);
+    /// ai.free_object(ptr);
+    /// ```
+    pub fn with_tag(&self, tag: &'static str) -> Allocator {
+        return TaggedAllocator::new_tagged(tag, self);
+    }
+}
+
+/// Usage statistics of a single tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TagStats {
+    /// Bytes currently allocated under the tag.
+    pub live_bytes: usize,
+    /// Number of allocations currently alive under the tag.
+    pub live_count: usize,
+    /// Highest amount of bytes that were allocated under the tag at once.
+    pub peak_bytes: usize,
+    /// Number of allocations ever made under the tag.
+    pub total_count: usize
+}
+
+#[derive(Default)]
+struct TrackerState {
+    tags: HashMap<&'static str, TagStats>,
+    // Totals over every tag.
+    live_bytes: usize,
+    peak_bytes: usize,
+    // Tag that each live allocation was counted under, so frees are credited correctly regardless of the current tag.
+    allocations: HashMap<usize, &'static str>
+}
+
+/// Per tag memory usage recorded by a `TrackingAllocator`.
+pub struct TagTracker {
+    state: Mutex<TrackerState>
+}
+
+impl TagTracker {
+    fn new() -> Self {
+        return TagTracker { state: Mutex::new(TrackerState::default()) };
+    }
+
+    /// Bytes currently allocated under `tag`.
+    pub fn live_bytes(&self, tag: &str) -> usize {
+        return self.stats(tag).live_bytes;
+    }
+
+    /// Number of allocations currently alive under `tag`.
+    pub fn live_count(&self, tag: &str) -> usize {
+        return self.stats(tag).live_count;
+    }
+
+    /// Statistics of `tag`. All zero if nothing was ever allocated under it.
+    pub fn stats(&self, tag: &str) -> TagStats {
+        return self.state.lock().unwrap().tags.get(tag).copied().unwrap_or_default();
+    }
+
+    /// Snapshot of every tag's usage, sorted by live bytes from most to least.
+    /// ```
+    /// # use gk_types_rs::allocator::tagging::{TrackingAllocator, TaggedAllocator};
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let (tracking, tracker) = TrackingAllocator::new_tracking(global_heap_allocator());
+    /// let ui = TaggedAllocator::new_tagged("ui", &tracking);
+    /// let audio = TaggedAllocator::new_tagged("audio", &tracking);
+    /// let a = ui.malloc_buffer::<u8>(10).unwrap();
+    /// let b = audio.malloc_buffer::<u8>(300).unwrap();
+    ///
+    /// let report = tracker.report();
+    /// assert_eq!(report.entries()[0].tag, "audio");
+    /// assert_eq!(report.entries()[1].tag, "ui");
+    /// assert_eq!(report.total_live_bytes(), 310);
+    /// println!("{}", report);
+    /// assert!(report.to_csv().starts_with("tag,live_bytes,live_count,peak_bytes,total_count\naudio,300,1,300,1\n"));
+    /// assert!(report.to_json().contains("{\"tag\":\"ui\",\"live_bytes\":10,\"live_count\":1,\"peak_bytes\":10,\"total_count\":1}"));
+    /// # ui.free_buffer(a, 10);
+    /// # audio.free_buffer(b, 300);
+    /// ```
+    pub fn report(&self) -> MemoryReport {
+        let state = self.state.lock().unwrap();
+        let mut entries: Vec<MemoryReportEntry> = state.tags.iter()
+            .map(|(tag, stats)| MemoryReportEntry { tag, stats: *stats })
+            .collect();
+        entries.sort_by(|a, b| b.stats.live_bytes.cmp(&a.stats.live_bytes).then(a.tag.cmp(b.tag)));
+        return MemoryReport { entries };
+    }
+
+    fn record_malloc(&self, ptr: *mut u8, size: usize) {
+        let tag = current_allocation_tag().unwrap_or(UNTAGGED);
+        let mut state = self.state.lock().unwrap();
+        state.allocations.insert(ptr as usize, tag);
+        let stats = state.tags.entry(tag).or_default();
+        stats.live_bytes += size;
+        stats.live_count += 1;
+        stats.total_count += 1;
+        stats.peak_bytes = stats.peak_bytes.max(stats.live_bytes);
+        state.live_bytes += size;
+        state.peak_bytes = state.peak_bytes.max(state.live_bytes);
+    }
+
+    fn record_free(&self, ptr: *mut u8, size: usize) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(tag) = state.allocations.remove(&(ptr as usize)) {
+            let stats = state.tags.get_mut(tag).unwrap();
+            stats.live_bytes -= size;
+            stats.live_count -= 1;
+            state.live_bytes -= size;
+        }
+    }
+
+    fn record_resize(&self, ptr: *mut u8, old_size: usize, new_size: usize) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(tag) = state.allocations.get(&(ptr as usize)).copied() {
+            let stats = state.tags.get_mut(tag).unwrap();
+            stats.live_bytes = stats.live_bytes - old_size + new_size;
+            stats.peak_bytes = stats.peak_bytes.max(stats.live_bytes);
+            state.live_bytes = state.live_bytes - old_size + new_size;
+            state.peak_bytes = state.peak_bytes.max(state.live_bytes);
+        }
+    }
+}
+
+/// Counts every allocation made through it under the allocation tag that is active when it is made,
+/// either from a `TaggedAllocator` above it, or from `push_allocation_tag()`.
+/// Keeps a record per live allocation, so it is meant for development and profiling builds.
+pub struct TrackingAllocator {
+    parent: Allocator,
+    tracker: Arc<TagTracker>
+}
+
+impl TrackingAllocator {
+    /// Creates a tracking allocator that allocates through `parent`, along with the shared tracker
+    /// used to query usage and produce reports.
+    pub fn new_tracking(parent: &Allocator) -> (Allocator, Arc<TagTracker>) {
+        let tracker = Arc::new(TagTracker::new());
+        let allocator = Allocator::from_boxed(Box::new(TrackingAllocator {
+            parent: parent.clone(),
+            tracker: tracker.clone()
+        }));
+        return (allocator, tracker);
+    }
+}
+
+impl AllocatorTrait for TrackingAllocator {
+    fn new_impl() -> Box<dyn AllocatorTrait>
+    where Self: Sized {
+        return Box::new(TrackingAllocator { parent: global_heap_allocator().clone(), tracker: Arc::new(TagTracker::new()) });
+    }
+
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        let ptr = self.parent.malloc(layout)?;
+        self.tracker.record_malloc(ptr, layout.size());
+        return Ok(ptr);
+    }
+
+    fn malloc_zero(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        let ptr = self.parent.malloc_zero(layout)?;
+        self.tracker.record_malloc(ptr, layout.size());
+        return Ok(ptr);
+    }
+
+    fn free(&self, ptr: *mut u8, layout: Layout) {
+        self.tracker.record_free(ptr, layout.size());
+        self.parent.free(ptr, layout);
+    }
+
+    fn owns(&self, ptr: *const u8) -> bool {
+        return self.parent.owns(ptr);
+    }
+
+    fn grow_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        if !self.parent.grow_in_place(ptr, layout, new_size) {
+            return false;
+        }
+        self.tracker.record_resize(ptr, layout.size(), new_size);
+        return true;
+    }
+
+    fn shrink_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        if !self.parent.shrink_in_place(ptr, layout, new_size) {
+            return false;
+        }
+        self.tracker.record_resize(ptr, layout.size(), new_size);
+        return true;
+    }
+
+    fn stats(&self) -> Option<AllocatorStats> {
+        let state = self.tracker.state.lock().unwrap();
+        return Some(AllocatorStats {
+            used_bytes: state.live_bytes,
+            peak_bytes: state.peak_bytes,
+            live_allocations: Some(state.allocations.len())
+        });
+    }
+}
+
+/// A single tag's row in a `MemoryReport`.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryReportEntry {
+    pub tag: &'static str,
+    pub stats: TagStats
+}
+
+/// Snapshot of per tag memory usage, sorted by live bytes from most to least.
+/// Displays as a table, and can be exported as JSON or CSV.
+#[derive(Debug, Clone)]
+pub struct MemoryReport {
+    entries: Vec<MemoryReportEntry>
+}
+
+impl MemoryReport {
+    /// Every tag's row, sorted by live bytes from most to least.
+    pub fn entries(&self) -> &[MemoryReportEntry] {
+        return &self.entries;
+    }
+
+    /// Sum of live bytes across every tag.
+    pub fn total_live_bytes(&self) -> usize {
+        return self.entries.iter().map(|entry| entry.stats.live_bytes).sum();
+    }
+
+    /// Prints the report table to stdout.
+    pub fn print(&self) {
+        print!("{}", self);
+    }
+
+    /// Exports the report as a JSON array of objects, one per tag.
+    pub fn to_json(&self) -> String {
+        let rows: Vec<String> = self.entries.iter().map(|entry| format!(
+            "{{\"tag\":\"{}\",\"live_bytes\":{},\"live_count\":{},\"peak_bytes\":{},\"total_count\":{}}}",
+            escape_json(entry.tag), entry.stats.live_bytes, entry.stats.live_count, entry.stats.peak_bytes, entry.stats.total_count
+        )).collect();
+        return format!("[{}]", rows.join(","));
+    }
+
+    /// Exports the report as CSV with a header row, one row per tag.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("tag,live_bytes,live_count,peak_bytes,total_count\n");
+        for entry in &self.entries {
+            csv.push_str(&format!("{},{},{},{},{}\n",
+                escape_csv(entry.tag), entry.stats.live_bytes, entry.stats.live_count, entry.stats.peak_bytes, entry.stats.total_count));
+        }
+        return csv;
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag_width = self.entries.iter().map(|entry| entry.tag.len()).max().unwrap_or(0).max(3);
+        writeln!(f, "{:<tag_width$} {:>14} {:>10} {:>14} {:>12}", "tag", "live bytes", "live count", "peak bytes", "total count")?;
+        for entry in &self.entries {
+            writeln!(f, "{:<tag_width$} {:>14} {:>10} {:>14} {:>12}",
+                entry.tag, entry.stats.live_bytes, entry.stats.live_count, entry.stats.peak_bytes, entry.stats.total_count)?;
+        }
+        return writeln!(f, "{:<tag_width$} {:>14}", "total", self.total_live_bytes());
+    }
+}
+
+fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    return escaped;
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    return value.to_string();
+}
@@ -0,0 +1,297 @@
+use super::{allocator::{AllocatorTrait, AllocErr, Allocator}, heap_allocator::global_heap_allocator};
+use std::{alloc::Layout, cell::RefCell, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc}};
+
+const MIN_CLASS_SIZE: usize = 16;
+const MAX_CLASS_SIZE: usize = 2048;
+const SIZE_CLASS_COUNT: usize = (MAX_CLASS_SIZE / MIN_CLASS_SIZE).trailing_zeros() as usize + 1;
+const MAX_CLASS_ALIGNMENT: usize = 64;
+/// Blocks held per size class per thread before half of them are given back to the parent.
+const MAGAZINE_CAPACITY: usize = 64;
+
+static NEXT_CACHE_ID: AtomicUsize = AtomicUsize::new(0);
+// Bumped whenever a `ThreadCacheAllocator` is dropped, so every thread knows to drop its caches for it.
+static DROPPED_CACHE_EPOCH: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD_CACHES: RefCell<ThreadCaches> = const { RefCell::new(ThreadCaches { caches: Vec::new(), seen_epoch: 0 }) };
+}
+
+/// Every cache of the calling thread, one per live `ThreadCacheAllocator` it has used.
+struct ThreadCaches {
+    caches: Vec<ThreadCache>,
+    // `DROPPED_CACHE_EPOCH` as of the last time stale caches were removed.
+    seen_epoch: usize
+}
+
+impl ThreadCaches {
+    /// Flushes and removes the caches of dropped allocators, if any allocator was dropped since the last check.
+    fn remove_stale(&mut self) {
+        let epoch = DROPPED_CACHE_EPOCH.load(Ordering::Acquire);
+        if epoch == self.seen_epoch {
+            return;
+        }
+        self.seen_epoch = epoch;
+        self.caches.retain(|cache| cache.is_alive.load(Ordering::Acquire));
+    }
+}
+
+/// Per thread free lists for one `ThreadCacheAllocator`.
+struct ThreadCache {
+    id: usize,
+    // Cleared once the allocator is dropped.
+    is_alive: Arc<AtomicBool>,
+    parent: Allocator,
+    magazines: [Vec<*mut u8>; SIZE_CLASS_COUNT]
+}
+
+impl ThreadCache {
+    fn new(id: usize, is_alive: &Arc<AtomicBool>, parent: &Allocator) -> Self {
+        return ThreadCache {
+            id,
+            is_alive: is_alive.clone(),
+            parent: parent.clone(),
+            magazines: std::array::from_fn(|_| Vec::with_capacity(MAGAZINE_CAPACITY))
+        }
+    }
+
+    fn flush(&mut self) {
+        for (class_index, magazine) in self.magazines.iter_mut().enumerate() {
+            let layout = class_layout(class_index);
+            for block in magazine.drain(..) {
+                self.parent.free(block, layout);
+            }
+        }
+    }
+}
+
+impl Drop for ThreadCache {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Index of the size class that can hold `layout`, or None if it is too large or too aligned to be cached.
+fn size_class_index(layout: Layout) -> Option<usize> {
+    if layout.align() > MAX_CLASS_ALIGNMENT {
+        return None;
+    }
+    let class_size = layout.size().max(layout.align()).max(MIN_CLASS_SIZE).next_power_of_two();
+    if class_size > MAX_CLASS_SIZE {
+        return None;
+    }
+    return Some((class_size / MIN_CLASS_SIZE).trailing_zeros() as usize);
+}
+
+/// Layout that every block of a size class is allocated from the parent with.
+fn class_layout(class_index: usize) -> Layout {
+    let class_size = MIN_CLASS_SIZE << class_index;
+    return unsafe { Layout::from_size_align_unchecked(class_size, class_size.min(MAX_CLASS_ALIGNMENT)) };
+}
+
+/// Caches small blocks per thread in front of a shared allocator, so repeated allocations and frees of small
+/// sizes do not touch the shared allocator at all. Requests up to 2048 bytes are rounded up to a power of two
+/// size class, and each thread keeps a magazine of free blocks per class. Larger requests go straight to the parent.
+///
+/// Each thread's cached blocks are given back to the parent when the thread exits, so job threads of a
+/// `JobSystem` flush automatically when they are shut down or the thread count changes.
+/// Once the allocator is dropped, every other thread gives its cached blocks back the next time it uses any thread
+/// caching allocator, or calls `release_stale_caches()`. Job threads call it whenever they run out of jobs, so long
+/// lived job threads only hold on to the caches of dropped allocators until they next run a job.
+/// A block may be freed on a different thread than it was allocated on.
+/// ```
+/// # use gk_types_rs::allocator::thread_cache_allocator::ThreadCacheAllocator;
+/// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// let (shared, budget) = BudgetAllocator::new_budget("shared", global_heap_allocator(), usize::MAX);
+/// let allocator = ThreadCacheAllocator::new_thread_cache(&shared);
+///
+/// let a = allocator.malloc_object::<[u8; 24]>().unwrap();
+/// allocator.free_object(a);
+/// // The block stays in this thread's cache rather than going back to the shared allocator.
+/// assert_eq!(budget.used(), 32);
+/// let b = allocator.malloc_object::<[u8; 24]>().unwrap();
+/// assert_eq!(a, b);
+/// allocator.free_object(b);
+///
+/// ThreadCacheAllocator::flush_current_thread();
+/// assert_eq!(budget.used(), 0);
+/// ```
+/// Works from within jobs, with each job thread getting its own cache.
+/// ```
+/// # use gk_types_rs::allocator::thread_cache_allocator::ThreadCacheAllocator;
+/// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+/// # use gk_types_rs::array::array_list::ArrayList;
+/// # use gk_types_rs::job_system::system::JobSystem;
+/// let allocator = ThreadCacheAllocator::new_thread_cache(global_heap_allocator());
+/// let job_system = JobSystem::new_init(2);
+/// let futures: Vec<_> = (0..4).map(|i| {
+///     let allocator = allocator.clone();
+///     job_system.run_job(move || {
+///         let mut array_list: ArrayList<String> = ArrayList::new(&allocator);
+///         array_list.push(i.to_string());
+///         array_list.len()
+///     })
+/// }).collect();
+/// for future in futures {
+///     assert_eq!(future.wait(), 1);
+/// }
+/// ```
+pub struct ThreadCacheAllocator {
+    id: usize,
+    is_alive: Arc<AtomicBool>,
+    parent: Allocator
+}
+
+impl ThreadCacheAllocator {
+    /// Creates a thread caching allocator in front of `parent`.
+    pub fn new_thread_cache(parent: &Allocator) -> Allocator {
+        return Allocator::from_boxed(Box::new(Self::new_with_parent(parent)));
+    }
+
+    /// Gives all blocks cached by the calling thread, for every thread caching allocator, back to their parents.
+    pub fn flush_current_thread() {
+        let _ = THREAD_CACHES.try_with(|caches| {
+            if let Ok(mut caches) = caches.try_borrow_mut() {
+                caches.remove_stale();
+                for cache in caches.caches.iter_mut() {
+                    cache.flush();
+                }
+            }
+        });
+    }
+
+    /// Gives the blocks the calling thread cached for dropped thread caching allocators back to their parents.
+    /// Cheap when no allocator was dropped since the last call. Called by job threads whenever they run out of jobs.
+    /// ```
+    /// # use gk_types_rs::allocator::thread_cache_allocator::ThreadCacheAllocator;
+    /// # use gk_types_rs::allocator::budget_allocator::BudgetAllocator;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let (shared, budget) = BudgetAllocator::new_budget("shared", global_heap_allocator(), usize::MAX);
+    /// let job_system = JobSystem::new_init(1);
+    /// let allocator = ThreadCacheAllocator::new_thread_cache(&shared);
+    /// let job_allocator = allocator.clone();
+    /// job_system.run_job(move || {
+    ///     let block = job_allocator.malloc_object::<u64>().unwrap();
+    ///     job_allocator.free_object(block);
+    /// }).wait();
+    /// // Cached on the job thread.
+    /// assert_eq!(budget.used(), 16);
+    /// drop(allocator);
+    /// // The job thread releases its cache once it next runs out of jobs.
+    /// job_system.run_job(|| ()).wait();
+    /// job_system.wait();
+    /// assert_eq!(budget.used(), 0);
+    /// ```
+    pub fn release_stale_caches() {
+        let _ = THREAD_CACHES.try_with(|caches| {
+            if let Ok(mut caches) = caches.try_borrow_mut() {
+                caches.remove_stale();
+            }
+        });
+    }
+
+    fn new_with_parent(parent: &Allocator) -> Self {
+        return ThreadCacheAllocator {
+            id: NEXT_CACHE_ID.fetch_add(1, Ordering::Relaxed),
+            is_alive: Arc::new(AtomicBool::new(true)),
+            parent: parent.clone()
+        }
+    }
+
+    /// Runs `func` on this allocator's cache for the calling thread. Returns None if the cache is unavailable,
+    /// such as during thread shutdown, or when the parent re-enters the cache from within `func`.
+    fn with_thread_cache<R>(&self, func: impl FnOnce(&mut ThreadCache) -> R) -> Option<R> {
+        return THREAD_CACHES.try_with(|caches| {
+            let mut caches = caches.try_borrow_mut().ok()?;
+            // Keeps the list down to the allocators that are alive, so the lookup stays short.
+            caches.remove_stale();
+            let caches = &mut caches.caches;
+            let index = match caches.iter().position(|cache| cache.id == self.id) {
+                Some(index) => index,
+                None => {
+                    caches.push(ThreadCache::new(self.id, &self.is_alive, &self.parent));
+                    caches.len() - 1
+                }
+            };
+            return Some(func(&mut caches[index]));
+        }).ok().flatten();
+    }
+}
+
+impl AllocatorTrait for ThreadCacheAllocator {
+    fn new_impl() -> Box<dyn AllocatorTrait>
+    where Self: Sized {
+        return Box::new(Self::new_with_parent(global_heap_allocator()));
+    }
+
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        let class_index = match size_class_index(layout) {
+            Some(class_index) => class_index,
+            None => return self.parent.malloc(layout)
+        };
+        if let Some(Some(block)) = self.with_thread_cache(|cache| cache.magazines[class_index].pop()) {
+            return Ok(block);
+        }
+        return self.parent.malloc(class_layout(class_index));
+    }
+
+    fn free(&self, ptr: *mut u8, layout: Layout) {
+        let class_index = match size_class_index(layout) {
+            Some(class_index) => class_index,
+            None => return self.parent.free(ptr, layout)
+        };
+        let class_layout = class_layout(class_index);
+        let cached = self.with_thread_cache(|cache| {
+            let magazine = &mut cache.magazines[class_index];
+            if magazine.len() == MAGAZINE_CAPACITY {
+                for block in magazine.drain(MAGAZINE_CAPACITY / 2..) {
+                    cache.parent.free(block, class_layout);
+                }
+            }
+            magazine.push(ptr);
+        });
+        if cached.is_none() {
+            self.parent.free(ptr, class_layout);
+        }
+    }
+
+    fn owns(&self, ptr: *const u8) -> bool {
+        return self.parent.owns(ptr);
+    }
+
+    /// Only allocations too large to be cached can be resized in place, by the parent.
+    fn grow_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        if size_class_index(layout).is_some() {
+            return false;
+        }
+        return self.parent.grow_in_place(ptr, layout, new_size);
+    }
+
+    /// Only allocations too large to be cached can be resized in place, by the parent.
+    fn shrink_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        if size_class_index(layout).is_some() || size_class_index(new_layout).is_some() {
+            return false;
+        }
+        return self.parent.shrink_in_place(ptr, layout, new_size);
+    }
+
+    /// Cached allocations can use their whole size class.
+    fn usable_size(&self, ptr: *const u8, layout: Layout) -> usize {
+        return match size_class_index(layout) {
+            Some(class_index) => class_layout(class_index).size(),
+            None => self.parent.usable_size(ptr, layout)
+        };
+    }
+}
+
+impl Drop for ThreadCacheAllocator {
+    fn drop(&mut self) {
+        // Set before bumping the epoch, so a thread that sees the new epoch also sees this allocator as dropped.
+        self.is_alive.store(false, Ordering::Release);
+        DROPPED_CACHE_EPOCH.fetch_add(1, Ordering::AcqRel);
+        // Other threads release their caches the next time they check.
+        Self::release_stale_caches();
+    }
+}
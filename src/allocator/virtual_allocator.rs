@@ -0,0 +1,114 @@
+use super::{allocator::{AllocatorTrait, AllocErr, Allocator}, virtual_memory::{page_size, round_to_page_size, reserve_pages, commit_pages, decommit_pages, release_pages}};
+use std::{alloc::Layout, ptr::NonNull};
+
+const DEFAULT_RESERVATION: usize = 64 * 1024 * 1024;
+
+/// Gives every allocation its own reserved range of virtual address space, of a fixed size set on creation.
+/// Only the pages that the allocation's size needs are committed, and the allocation can grow in place
+/// up to the reservation size by committing more pages, so its address never changes.
+/// Shrinking in place decommits the pages that are no longer needed.
+///
+/// Allocations larger than the reservation, or aligned to more than the page size, fail.
+/// Each allocation uses at least one page, so this is meant for a small number of large growable buffers,
+/// such as `ArrayList::new_stable()`.
+/// ```
+/// # use gk_types_rs::allocator::virtual_allocator::VirtualAllocator;
+/// # use std::alloc::Layout;
+/// let allocator = VirtualAllocator::new_virtual(1024 * 1024);
+/// let layout = Layout::from_size_align(100, 8).unwrap();
+/// let ptr = allocator.malloc(layout).unwrap();
+/// assert!(allocator.grow_in_place(ptr, layout, 512 * 1024));
+/// unsafe { *ptr.add(512 * 1024 - 1) = 1; }
+/// // Beyond the reservation.
+/// assert!(!allocator.grow_in_place(ptr, layout, 2 * 1024 * 1024));
+/// allocator.free(ptr, Layout::from_size_align(512 * 1024, 8).unwrap());
+/// ```
+pub struct VirtualAllocator {
+    reservation: usize
+}
+
+impl VirtualAllocator {
+    /// Creates an allocator that reserves `reservation` bytes (rounded up to the page size) of address space per allocation.
+    /// A reservation too large to round up is rounded down instead, and reserving it fails like any other reservation
+    /// larger than the address space.
+    pub fn new_virtual(reservation: usize) -> Allocator {
+        let reservation = round_to_page_size(reservation.max(1)).unwrap_or(usize::MAX & !(page_size() - 1));
+        return Allocator::from_boxed(Box::new(VirtualAllocator { reservation }));
+    }
+}
+
+impl AllocatorTrait for VirtualAllocator {
+    fn new_impl() -> Box<dyn AllocatorTrait>
+    where Self: Sized {
+        return Box::new(VirtualAllocator { reservation: round_to_page_size(DEFAULT_RESERVATION).unwrap() });
+    }
+
+    fn malloc(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        if layout.align() > page_size() {
+            return Err(AllocErr::AlignmentUnsupported { layout, allocator: self.name() });
+        }
+        if layout.size() > self.reservation {
+            return Err(AllocErr::Exhausted { layout, allocator: self.name() });
+        }
+        // Cannot fail, since the size is within the page rounded reservation.
+        let committed = round_to_page_size(layout.size())?;
+        unsafe {
+            let Some(ptr) = reserve_pages(self.reservation) else {
+                return Err(AllocErr::OutOfMemory { layout, allocator: self.name() });
+            };
+            if committed != 0 && !commit_pages(ptr, committed) {
+                release_pages(ptr, self.reservation);
+                return Err(AllocErr::OutOfMemory { layout, allocator: self.name() });
+            }
+            return Ok(ptr.as_ptr());
+        }
+    }
+
+    /// Freshly committed pages are always zeroed, so no extra work is needed.
+    fn malloc_zero(&self, layout: Layout) -> Result<*mut u8, AllocErr> {
+        return self.malloc(layout);
+    }
+
+    fn free(&self, ptr: *mut u8, _layout: Layout) {
+        // Null is never handed out, so there is nothing to release.
+        let Some(ptr) = NonNull::new(ptr) else {
+            return;
+        };
+        unsafe { release_pages(ptr, self.reservation) };
+    }
+
+    fn grow_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        let Some(ptr) = NonNull::new(ptr) else {
+            return false;
+        };
+        if new_size > self.reservation {
+            return false;
+        }
+        // Neither can fail, since both sizes are within the page rounded reservation.
+        let (Ok(old_committed), Ok(new_committed)) = (round_to_page_size(layout.size()), round_to_page_size(new_size)) else {
+            return false;
+        };
+        if new_committed <= old_committed {
+            return true;
+        }
+        return unsafe { commit_pages(ptr.add(old_committed), new_committed - old_committed) };
+    }
+
+    fn shrink_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        let Some(ptr) = NonNull::new(ptr) else {
+            return false;
+        };
+        let (Ok(old_committed), Ok(new_committed)) = (round_to_page_size(layout.size()), round_to_page_size(new_size)) else {
+            return false;
+        };
+        if new_committed < old_committed {
+            unsafe { decommit_pages(ptr.add(new_committed), old_committed - new_committed) };
+        }
+        return true;
+    }
+
+    /// Whole pages are committed, so the rest of the last page is usable too.
+    fn usable_size(&self, _ptr: *const u8, layout: Layout) -> usize {
+        return round_to_page_size(layout.size()).unwrap_or(layout.size());
+    }
+}
@@ -0,0 +1,211 @@
+use super::allocator::AllocErr;
+use std::{alloc::Layout, ptr::NonNull, sync::OnceLock};
+
+/// Size in bytes of a virtual memory page on this system.
+/// ```
+/// # use gk_types_rs::allocator::virtual_memory::page_size;
+/// assert!(page_size().is_power_of_two());
+/// ```
+pub fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+    return *PAGE_SIZE.get_or_init(platform::page_size);
+}
+
+/// Rounds `bytes` up to a multiple of the page size. Fails with `AllocErr::InvalidLayout` if that does not fit in a `usize`.
+/// ```
+/// # use gk_types_rs::allocator::virtual_memory::{round_to_page_size, page_size};
+/// assert_eq!(round_to_page_size(1).unwrap(), page_size());
+/// assert_eq!(round_to_page_size(page_size()).unwrap(), page_size());
+/// assert!(round_to_page_size(usize::MAX).is_err());
+/// ```
+pub fn round_to_page_size(bytes: usize) -> Result<usize, AllocErr> {
+    let page_mask = page_size() - 1;
+    return bytes.checked_add(page_mask).map(|padded| padded & !page_mask)
+        .ok_or(AllocErr::InvalidLayout { size: bytes, align: page_size(), allocator: "virtual memory" });
+}
+
+/// A range of reserved virtual address space. Reserving does not use any physical memory,
+/// which is only used once pages are committed. The address of the range never changes,
+/// so it can be grown by committing more pages without moving anything stored in it.
+/// The whole range is released when dropped.
+/// ```
+/// # use gk_types_rs::allocator::virtual_memory::{VirtualRange, page_size};
+/// let mut range = VirtualRange::reserve(1024 * 1024 * 1024).unwrap();
+/// let start = range.as_ptr();
+/// range.commit(100).unwrap();
+/// assert_eq!(range.committed_size(), page_size());
+/// unsafe { *start.add(99) = 1; }
+///
+/// range.commit(page_size() * 4).unwrap();
+/// assert_eq!(range.as_ptr(), start);
+/// assert_eq!(unsafe { *start.add(99) }, 1);
+///
+/// range.decommit(0);
+/// assert_eq!(range.committed_size(), 0);
+/// ```
+pub struct VirtualRange {
+    base: *mut u8,
+    reserved: usize,
+    committed: usize
+}
+
+/// Page aligned layout of `bytes`, used to describe failed reservations and commits.
+fn range_layout(bytes: usize) -> Result<Layout, AllocErr> {
+    return Layout::from_size_align(bytes, page_size())
+        .map_err(|_| AllocErr::InvalidLayout { size: bytes, align: page_size(), allocator: "VirtualRange" });
+}
+
+unsafe impl Send for VirtualRange {}
+unsafe impl Sync for VirtualRange {}
+
+impl VirtualRange {
+    /// Reserves at least `bytes` of address space, rounded up to the page size. Nothing is committed.
+    pub fn reserve(bytes: usize) -> Result<Self, AllocErr> {
+        let reserved = round_to_page_size(bytes.max(1))?;
+        let layout = range_layout(reserved)?;
+        let base = unsafe { platform::reserve(reserved) };
+        if base.is_null() {
+            return Err(AllocErr::OutOfMemory { layout, allocator: "VirtualRange" });
+        }
+        return Ok(VirtualRange { base, reserved, committed: 0 });
+    }
+
+    /// Start of the range. Only the first `committed_size()` bytes may be accessed.
+    pub fn as_ptr(&self) -> *mut u8 {
+        return self.base;
+    }
+
+    /// Bytes of address space reserved.
+    pub fn reserved_size(&self) -> usize {
+        return self.reserved;
+    }
+
+    /// Bytes from the start of the range that are committed and accessible.
+    pub fn committed_size(&self) -> usize {
+        return self.committed;
+    }
+
+    /// Commits pages so that at least the first `bytes` of the range are accessible.
+    /// Does nothing if they already are. Newly committed memory is zeroed.
+    pub fn commit(&mut self, bytes: usize) -> Result<(), AllocErr> {
+        let new_committed = round_to_page_size(bytes)?;
+        if new_committed <= self.committed {
+            return Ok(());
+        }
+        let layout = range_layout(new_committed)?;
+        if new_committed > self.reserved {
+            return Err(AllocErr::Exhausted { layout, allocator: "VirtualRange" });
+        }
+        let ok = unsafe { platform::commit(self.base.add(self.committed), new_committed - self.committed) };
+        if !ok {
+            return Err(AllocErr::OutOfMemory { layout, allocator: "VirtualRange" });
+        }
+        self.committed = new_committed;
+        return Ok(());
+    }
+
+    /// Decommits every page past the first `bytes` of the range, returning the physical memory to the system.
+    /// The address space stays reserved. Does nothing if `bytes` is not less than the committed size.
+    pub fn decommit(&mut self, bytes: usize) {
+        // Too large to round means it is past the committed size too.
+        let Ok(new_committed) = round_to_page_size(bytes) else {
+            return;
+        };
+        if new_committed >= self.committed {
+            return;
+        }
+        unsafe { platform::decommit(self.base.add(new_committed), self.committed - new_committed) };
+        self.committed = new_committed;
+    }
+}
+
+impl Drop for VirtualRange {
+    fn drop(&mut self) {
+        unsafe { platform::release(self.base, self.reserved) };
+    }
+}
+
+/// Commits `bytes` starting at `ptr`, which must be page aligned and within a reserved range.
+pub(crate) unsafe fn commit_pages(ptr: NonNull<u8>, bytes: usize) -> bool {
+    return platform::commit(ptr.as_ptr(), bytes);
+}
+
+/// Decommits `bytes` starting at `ptr`, which must be page aligned and within a reserved range.
+pub(crate) unsafe fn decommit_pages(ptr: NonNull<u8>, bytes: usize) {
+    platform::decommit(ptr.as_ptr(), bytes);
+}
+
+/// Reserves `bytes` of address space, returning None on failure.
+pub(crate) unsafe fn reserve_pages(bytes: usize) -> Option<NonNull<u8>> {
+    return NonNull::new(platform::reserve(bytes));
+}
+
+/// Releases a whole range previously returned by `reserve_pages()`.
+pub(crate) unsafe fn release_pages(ptr: NonNull<u8>, bytes: usize) {
+    platform::release(ptr.as_ptr(), bytes);
+}
+
+#[cfg(unix)]
+mod platform {
+    pub(super) fn page_size() -> usize {
+        return unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+    }
+
+    pub(super) unsafe fn reserve(bytes: usize) -> *mut u8 {
+        let ptr = libc::mmap(
+            std::ptr::null_mut(),
+            bytes,
+            libc::PROT_NONE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+            -1,
+            0
+        );
+        if ptr == libc::MAP_FAILED {
+            return std::ptr::null_mut();
+        }
+        return ptr as *mut u8;
+    }
+
+    pub(super) unsafe fn commit(ptr: *mut u8, bytes: usize) -> bool {
+        return libc::mprotect(ptr as *mut libc::c_void, bytes, libc::PROT_READ | libc::PROT_WRITE) == 0;
+    }
+
+    pub(super) unsafe fn decommit(ptr: *mut u8, bytes: usize) {
+        // Drops the physical pages, so they read back as zero if committed again.
+        libc::madvise(ptr as *mut libc::c_void, bytes, libc::MADV_DONTNEED);
+        libc::mprotect(ptr as *mut libc::c_void, bytes, libc::PROT_NONE);
+    }
+
+    pub(super) unsafe fn release(ptr: *mut u8, bytes: usize) {
+        libc::munmap(ptr as *mut libc::c_void, bytes);
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use winapi::um::{memoryapi::{VirtualAlloc, VirtualFree}, sysinfoapi::{GetSystemInfo, SYSTEM_INFO}, winnt::{MEM_COMMIT, MEM_DECOMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_NOACCESS, PAGE_READWRITE}};
+
+    pub(super) fn page_size() -> usize {
+        unsafe {
+            let mut info: SYSTEM_INFO = std::mem::zeroed();
+            GetSystemInfo(&mut info);
+            return info.dwPageSize as usize;
+        }
+    }
+
+    pub(super) unsafe fn reserve(bytes: usize) -> *mut u8 {
+        return VirtualAlloc(std::ptr::null_mut(), bytes, MEM_RESERVE, PAGE_NOACCESS) as *mut u8;
+    }
+
+    pub(super) unsafe fn commit(ptr: *mut u8, bytes: usize) -> bool {
+        return !VirtualAlloc(ptr as *mut _, bytes, MEM_COMMIT, PAGE_READWRITE).is_null();
+    }
+
+    pub(super) unsafe fn decommit(ptr: *mut u8, bytes: usize) {
+        VirtualFree(ptr as *mut _, bytes, MEM_DECOMMIT);
+    }
+
+    pub(super) unsafe fn release(ptr: *mut u8, _bytes: usize) {
+        VirtualFree(ptr as *mut _, 0, MEM_RELEASE);
+    }
+}
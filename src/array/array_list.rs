@@ -1,6 +1,7 @@
 use core::panic;
-use std::{mem::{size_of, ManuallyDrop, align_of, MaybeUninit}, marker::PhantomData, ops::{Index, IndexMut}, sync::Once};
-use crate::{allocator::heap_allocator::global_heap_allocator, cpu_features::{is_avx512_supported, is_avx2_supported}};
+use std::{alloc::Layout, mem::{size_of, ManuallyDrop, align_of, MaybeUninit}, marker::PhantomData, ops::{Index, IndexMut}, sync::Once};
+use crate::{allocator::{named_allocators::current_allocator, virtual_allocator::VirtualAllocator}, cpu_features::{is_avx512_supported, is_avx2_supported},
+    job_system::{system::JobSystem, parallel::{ParIter, ParIterMut}}};
 use super::super::allocator::allocator::Allocator;
 
 // is size of pointer + usize
@@ -211,6 +212,16 @@ impl<T> ArrayList<T> {
     /// let array_list: ArrayList<u32> = ArrayList::with_capacity(global_heap_allocator(), 3);
     /// assert_eq!(array_list.capacity(), 4);
     /// ```
+    /// Claims any slack the allocator has, such as when it rounds up to a size class.
+    /// ```
+    /// # use gk_types_rs::array::array_list::ArrayList;
+    /// # use gk_types_rs::allocator::thread_cache_allocator::ThreadCacheAllocator;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// let allocator = ThreadCacheAllocator::new_thread_cache(global_heap_allocator());
+    /// // 3 * 24 bytes is rounded up to the 128 byte size class, which holds 5 Strings.
+    /// let array_list: ArrayList<String> = ArrayList::with_capacity(&allocator, 3);
+    /// assert_eq!(array_list.capacity(), 5);
+    /// ```
     pub fn with_capacity(allocator: &Allocator, mut capacity: usize) -> Self {
         if capacity == 0 {
             return ArrayList::new(allocator);
@@ -229,6 +240,38 @@ impl<T> ArrayList<T> {
         return array_list;
     }
 
+    /// Creates a new ArrayList whose elements never move in memory as it grows, so pointers to elements stay valid
+    /// until they are removed. Reserves virtual address space for at least `max_capacity` elements up front,
+    /// and only commits physical memory as the ArrayList grows. Shrinking returns the unused memory to the system.
+    /// Does not use the in-place buffer, because it moves along with the ArrayList itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ArrayList needs to grow beyond its reserved address space, which is only possible
+    /// once it holds more than `max_capacity` elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use gk_types_rs::array::array_list::ArrayList;
+    /// let mut array_list: ArrayList<u64> = ArrayList::new_stable(1_000_000);
+    /// array_list.push(0);
+    /// let first = &array_list[0] as *const u64;
+    /// for i in 1..100_000 {
+    ///     array_list.push(i);
+    /// }
+    /// assert_eq!(&array_list[0] as *const u64, first);
+    /// ```
+    pub fn new_stable(max_capacity: usize) -> Self {
+        // Room for the last ~1.5x growth past max_capacity, plus SIMD rounding.
+        let reserved_capacity = max_capacity.saturating_add(max_capacity / 2).saturating_add(64);
+        let allocator = VirtualAllocator::new_virtual(reserved_capacity.saturating_mul(size_of::<T>()));
+        let mut array_list = ArrayList::new(&allocator);
+        array_list.rep = ArrayListRep { heap: ManuallyDrop::new(HeapRep::new()) };
+        array_list.length.set_heap_flag(true);
+        return array_list;
+    }
+
     /* 
     pub unsafe fn from_raw_parts(ptr: *mut T, length: usize, capacity: usize, allocator: &'a Allocator) -> Self {
         todo!()
@@ -805,6 +848,54 @@ impl<T> ArrayList<T> {
         return unsafe { std::slice::from_raw_parts_mut(self.as_mut_ptr(), self.len()) };
     }
 
+    /// Makes a parallel iterator over the elements, which runs in chunks on the job threads of `job_system`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::array::array_list::ArrayList;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use std::sync::atomic::{AtomicU32, Ordering};
+    /// let job_system = JobSystem::new_init(2);
+    /// let mut array_list: ArrayList<u32> = ArrayList::new(global_heap_allocator());
+    /// for i in 0..100 {
+    ///     array_list.push(i);
+    /// }
+    /// let even_count = AtomicU32::new(0);
+    /// array_list.par_iter(&job_system).for_each(|element| {
+    ///     if element % 2 == 0 {
+    ///         even_count.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// });
+    /// assert_eq!(even_count.load(Ordering::Relaxed), 50);
+    /// ```
+    pub fn par_iter<'a>(&'a self, job_system: &'a JobSystem) -> ParIter<'a, T>
+    where T: Sync {
+        return ParIter::new(job_system, self.as_slice());
+    }
+
+    /// Makes a parallel iterator over mutable references to the elements, which runs in chunks on the job threads of `job_system`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::array::array_list::ArrayList;
+    /// # use gk_types_rs::allocator::heap_allocator::global_heap_allocator;
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let mut array_list: ArrayList<u32> = ArrayList::new(global_heap_allocator());
+    /// for i in 0..100 {
+    ///     array_list.push(i);
+    /// }
+    /// array_list.par_iter_mut(&job_system).with_grain(10).for_each(|element| *element += 1);
+    /// assert_eq!(array_list[99], 100);
+    /// ```
+    pub fn par_iter_mut<'a>(&'a mut self, job_system: &'a JobSystem) -> ParIterMut<'a, T>
+    where T: Send {
+        return ParIterMut::new(job_system, self.as_mut_slice());
+    }
+
     /// Get a reference to the allocator this ArrayList is using. Can be cloned.
     pub fn allocator(&self) -> &Allocator {
         return &self.allocator;
@@ -896,6 +987,11 @@ impl<T> ArrayList<T> {
 
     fn reallocate(&mut self, mut min_capacity: usize) {
         let current_length = self.len() as isize;
+        if !self.is_small_rep() && self.rep.heap_buffer() != std::ptr::null() {
+            if self.try_resize_heap_buffer_in_place(min_capacity) {
+                return;
+            }
+        }
         let new_data: *mut T = Self::malloc_heap_buffer(&self.allocator, &mut min_capacity);
         if !self.is_small_rep() { // is already heap, will need to move all old elements into new buffer and update union members.
             if self.rep.heap_buffer() != std::ptr::null() {               
@@ -921,30 +1017,80 @@ impl<T> ArrayList<T> {
         self.length.set_heap_flag(true);
     }
 
+    /// Asks the allocator to grow or shrink the existing heap buffer without moving it, such as for `new_stable()`.
+    /// Uses the same SIMD capacity rounding and alignment as `malloc_heap_buffer()`.
+    fn try_resize_heap_buffer_in_place(&mut self, mut new_capacity: usize) -> bool {
+        let can_simd = const { size_of::<T>() == 1 || size_of::<T>() == 2 || size_of::<T>() == 4 || size_of::<T>() == 8 };
+        let alignment = if can_simd {
+            let num_per_simd = 64 / size_of::<T>();
+            let remainder = new_capacity % num_per_simd;
+            if remainder != 0 {
+                new_capacity = new_capacity + (num_per_simd - remainder);
+            }
+            64
+        }
+        else {
+            align_of::<T>()
+        };
+
+        let current_capacity = unsafe { self.rep.heap.capacity };
+        let (old_size, new_size) = match (size_of::<T>().checked_mul(current_capacity), size_of::<T>().checked_mul(new_capacity)) {
+            (Some(old_size), Some(new_size)) => (old_size, new_size),
+            _ => return false
+        };
+        let old_layout = unsafe { Layout::from_size_align_unchecked(old_size, alignment) };
+        let buffer = self.rep.heap_buffer_mut() as *mut u8;
+        let resized = if new_size >= old_size {
+            self.allocator.grow_in_place(buffer, old_layout, new_size)
+        }
+        else {
+            self.allocator.shrink_in_place(buffer, old_layout, new_size)
+        };
+        if resized {
+            self.rep.heap_set_capacity(new_capacity);
+        }
+        return resized;
+    }
+
     /// Will allocate for a buffer on the heap. If the type can be used for SIMD operations, the allocation will be 64 byte aligned, 
-    /// and will contain chunks of 64 / size_of::<T>().
+    /// and will contain chunks of 64 / size_of::<T>(). Any slack the allocator reports through `usable_size()`
+    /// is claimed as extra capacity.
     fn malloc_heap_buffer(allocator: &Allocator, capacity: &mut usize) -> *mut T {
         let can_simd = const { size_of::<T>() == 1 || size_of::<T>() == 2 || size_of::<T>() == 4 || size_of::<T>() == 8 };
-        if can_simd {
+        let buffer: *mut T = if can_simd {
             let num_per_simd = 64 / size_of::<T>();
             let remainder = *capacity % num_per_simd;
             if remainder != 0 {
                 *capacity = *capacity + (num_per_simd - remainder);
             }
-            return allocator.malloc_aligned_buffer(*capacity, 64).unwrap();
+            allocator.malloc_aligned_buffer(*capacity, 64).unwrap()
         }
         else {
-            return allocator.malloc_buffer(*capacity).unwrap();
+            allocator.malloc_buffer(*capacity).unwrap()
+        };
+
+        if size_of::<T>() != 0 {
+            let layout = unsafe { Layout::from_size_align_unchecked(*capacity * size_of::<T>(), Self::heap_buffer_alignment()) };
+            let mut usable_capacity = allocator.usable_size(buffer as *const u8, layout) / size_of::<T>();
+            if can_simd {
+                usable_capacity -= usable_capacity % (64 / size_of::<T>());
+            }
+            *capacity = usable_capacity.max(*capacity);
         }
+        return buffer;
     }
 
     fn free_heap_buffer(allocator: &Allocator, buffer: *mut T, capacity: usize) {
-        if size_of::<T>() <= size_of::<usize>() { // can be used for SIMD
-            return allocator.free_aligned_buffer(buffer, capacity, 64);
-        }
-        else {
-            return allocator.free_buffer(buffer, capacity);
+        return allocator.free_aligned_buffer(buffer, capacity, Self::heap_buffer_alignment());
+    }
+
+    /// Alignment of heap buffers. 64 bytes for types that can be used for SIMD operations.
+    const fn heap_buffer_alignment() -> usize {
+        let can_simd = size_of::<T>() == 1 || size_of::<T>() == 2 || size_of::<T>() == 4 || size_of::<T>() == 8;
+        if can_simd {
+            return 64;
         }
+        return align_of::<T>();
     }
 
     fn do_simd_find(buffer: *const T, length: usize, capacity: usize, element: &T) -> Option<usize> {
@@ -992,9 +1138,10 @@ impl<T> ArrayList<T> {
     }
 }
 
+/// Uses the calling thread's current allocator. See `push_current_allocator()`.
 impl<T> Default for ArrayList<T> {
     fn default() -> Self {
-        return ArrayList::new(global_heap_allocator());
+        return ArrayList::new(&current_allocator());
     }
 }
 
@@ -1,45 +1,35 @@
-use super::{system::QUEUE_CAPACITY, job_container::JobContainer, ring_queue::JobRingQueue};
+use std::collections::VecDeque;
+use super::{job_container::JobContainer, JobQueue, priority::{StarvationGuard, PRIORITY_COUNT}};
 
 pub(crate) struct ActiveJobs {
-    work: Box<[JobContainer]>,
-    count: usize
+    // One per `JobPriority`, most urgent first.
+    work: [VecDeque<JobContainer>; PRIORITY_COUNT],
+    starvation_guard: StarvationGuard
 }
 
 impl ActiveJobs {
-    pub(crate) fn new() -> Self {
-        let mut v: Vec<JobContainer> = Vec::with_capacity(QUEUE_CAPACITY);
-        for _ in 0..QUEUE_CAPACITY {
-            v.push(JobContainer::default());
-        }
-        return ActiveJobs { 
-            work: v.into_boxed_slice(),
-            count: 0
+    /// The work buffers start empty, and grow to fit what is collected, so priorities that are never used take no memory.
+    pub(crate) fn new(starvation_limit: usize) -> Self {
+        return ActiveJobs {
+            work: std::array::from_fn(|_| VecDeque::new()),
+            starvation_guard: StarvationGuard::new(starvation_limit)
         }
     }
 
-    pub(crate) fn collect_jobs(&mut self, queue: &mut JobRingQueue) {
-        debug_assert!((self.count + queue.length) <= QUEUE_CAPACITY, "Too many job to be worked on");
-        // All jobs in the unused part of the work array should have no bind.
-        unsafe { 
-            let work_ptr = self.work.as_mut_ptr();
-            let queue_buf_ptr = queue.buffer.as_mut_ptr();
-            std::ptr::swap_nonoverlapping(work_ptr.offset(self.count as isize), queue_buf_ptr, queue.length);
-            // From the behaviour of invoke_all_jobs() replacing self's active work buffer with Job::default(), it can be assumed that swap will correct change the queue to hold default
-            //std::ptr::write(&mut queue.buffer as *mut Job, Job::default());           
+    /// Moves every queued job into the active work in one batch per priority, growing the work buffers if the queues
+    /// have overflowed. Returns how many jobs were collected. Must only be called by the job thread consuming `queues`.
+    pub(crate) unsafe fn collect_jobs(&mut self, queues: &[JobQueue; PRIORITY_COUNT]) -> usize {
+        let mut collected_count = 0;
+        for (work, queue) in self.work.iter_mut().zip(queues.iter()) {
+            collected_count += queue.collect_into(work);
         }
-        self.count += queue.length;
-        queue.length = 0;
-        queue.read_index = 0;
-        queue.write_index = 0;
+        return collected_count;
     }
 
-    pub(crate) fn invoke_all_jobs(&mut self) {
-        for i in 0..self.count {
-            self.work[i].invoke();
-            //let job = std::mem::take(&mut self.work[i]);
-            //job.invoke();
-            // is dropped
-        }
-        self.count = 0;
+    /// Takes the most urgent job, unless a less urgent one has waited for too long.
+    pub(crate) fn take_next_job(&mut self) -> Option<JobContainer> {
+        let has_work = std::array::from_fn(|index| !self.work[index].is_empty());
+        let priority_index = self.starvation_guard.next_priority(has_work)?;
+        return self.work[priority_index].pop_front();
     }
-}
\ No newline at end of file
+}
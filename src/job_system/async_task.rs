@@ -0,0 +1,104 @@
+use std::{future::Future, pin::Pin, sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}, task::{Context, Poll, Wake, Waker}};
+use super::{future::{JobFuture, WithinJobFuture, catch_job_panic}, graph::ReleaseJob, job_container::JobContainer, settings::PanicPolicy};
+
+/// Called once a spawned future has completed. Given by the `JobSystem`, which waits for every spawned future.
+pub(crate) type FinishTask = Box<dyn Fn() + Send + Sync>;
+
+// States of an `AsyncTask`. Only one poll job is ever queued or running, so the future is never polled concurrently.
+const IDLE: usize = 0;
+const SCHEDULED: usize = 1;
+const POLLING: usize = 2;
+// Woken while polling, so it is polled again once the current poll returns.
+const REPOLL: usize = 3;
+const DONE: usize = 4;
+
+/// A future from `JobSystem::spawn_async()`, polled in a job every time it is woken.
+pub(crate) struct AsyncTask<T> {
+    // Only locked by the poll job, so it is never contended. Emptied once the future completes.
+    future: Mutex<Option<Pin<Box<dyn Future<Output = T> + Send>>>>,
+    state: AtomicUsize,
+    completion: WithinJobFuture<T>,
+    // Queues poll jobs.
+    schedule: ReleaseJob,
+    finish: FinishTask,
+    panic_policy: PanicPolicy
+}
+
+impl<T> AsyncTask<T>
+where T: Send + 'static {
+    /// Queues the first poll of `future`, and returns the future holding its output.
+    pub(crate) fn spawn<F>(future: F, schedule: ReleaseJob, finish: FinishTask, panic_policy: PanicPolicy) -> JobFuture<T>
+    where F: Future<Output = T> + Send + 'static {
+        let (wait_future, completion) = WithinJobFuture::new();
+        let task = Arc::new(AsyncTask {
+            future: Mutex::new(Some(Box::pin(future))),
+            state: AtomicUsize::new(SCHEDULED),
+            completion,
+            schedule,
+            finish,
+            panic_policy
+        });
+        task.schedule_poll();
+        return wait_future;
+    }
+
+    fn schedule_poll(self: &Arc<Self>) {
+        let task = self.clone();
+        (self.schedule)(JobContainer::new(move || task.poll()));
+    }
+
+    fn poll(self: &Arc<Self>) {
+        self.state.store(POLLING, Ordering::Release);
+        let waker = Waker::from(self.clone());
+        let mut context = Context::from_waker(&waker);
+        loop {
+            let mut future = self.future.lock().unwrap();
+            let pinned = future.as_mut().expect("Completed async task was polled");
+            let data = match catch_job_panic(self.panic_policy, || pinned.as_mut().poll(&mut context)) {
+                Ok(Poll::Pending) => None,
+                Ok(Poll::Ready(value)) => Some(Ok(value)),
+                Err(error) => Some(Err(error))
+            };
+            let Some(data) = data else {
+                drop(future);
+                if self.state.compare_exchange(POLLING, IDLE, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                    return;
+                }
+                self.state.store(POLLING, Ordering::Release);
+                continue;
+            };
+            // Dropped first, so whatever the future holds is released before anyone sees its output.
+            *future = None;
+            drop(future);
+            // Set before completing, so late wakes never schedule another poll.
+            self.state.store(DONE, Ordering::Release);
+            self.completion.set(data);
+            (self.finish)();
+            return;
+        }
+    }
+}
+
+impl<T> Wake for AsyncTask<T>
+where T: Send + 'static {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        loop {
+            let (current, next) = match self.state.load(Ordering::Acquire) {
+                IDLE => (IDLE, SCHEDULED),
+                POLLING => (POLLING, REPOLL),
+                // Already going to be polled, or complete.
+                _ => return
+            };
+            if self.state.compare_exchange(current, next, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                if next == SCHEDULED {
+                    self.schedule_poll();
+                }
+                return;
+            }
+        }
+    }
+}
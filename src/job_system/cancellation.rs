@@ -0,0 +1,139 @@
+use std::{fmt, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, Weak}};
+use super::{future::{JobError, WithinJobFuture, catch_job_panic}, settings::PanicPolicy};
+
+/// Cancels jobs run with `JobSystem::run_cancellable_job()`. Clones share the same cancellation, so one can be
+/// kept by whatever decides a job's result is stale, such as a change in game state.
+/// A job that has not started when its token is cancelled is dropped without running, and its `JobFuture` resolves to
+/// `JobError::Cancelled` right away, without waiting for a job thread to reach it. Jobs that have started are given
+/// the token to check, and return early if they choose to.
+/// ```
+/// # use gk_types_rs::job_system::{system::JobSystem, cancellation::CancellationToken, future::JobError};
+/// # use std::sync::{mpsc, Arc};
+/// let job_system = JobSystem::new_init(1);
+/// let (unblock, blocked) = mpsc::channel::<()>();
+/// // Keeps the only job thread busy, so the path request stays queued.
+/// let busy = job_system.run_job(move || blocked.recv().unwrap());
+/// let token = CancellationToken::new();
+/// let nav_grid = Arc::new(vec![0u8; 64 * 64]);
+/// let job_grid = nav_grid.clone();
+/// let path = job_system.run_cancellable_job(&token, move |_| -> Vec<(i32, i32)> { unreachable!("{}", job_grid.len()) });
+/// // The player moved, so the path is no longer needed.
+/// token.cancel();
+/// // Resolved, and the job dropped along with what it captured, without waiting for the job thread.
+/// assert!(matches!(path.try_wait(), Err(JobError::Cancelled)));
+/// assert_eq!(Arc::strong_count(&nav_grid), 1);
+/// unblock.send(()).unwrap();
+/// busy.wait();
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<TokenState>
+}
+
+#[derive(Default)]
+struct TokenState {
+    is_cancelled: AtomicBool,
+    // Queued jobs given the token. Jobs that have been run or dropped are pruned as more are added.
+    jobs: Mutex<Vec<Weak<dyn CancelJob>>>
+}
+
+/// A queued job that cancelling its token drops right away.
+pub(crate) trait CancelJob: Send + Sync {
+    fn cancel(&self);
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        return CancellationToken::default();
+    }
+
+    /// Cancels every job given this token, or a clone of it. Cannot be undone.
+    /// Jobs that have not started yet are dropped, and their futures resolved, on the calling thread.
+    pub fn cancel(&self) {
+        // Set before taking the jobs, so a job added after this sees it, and cancels itself instead.
+        self.state.is_cancelled.store(true, Ordering::Release);
+        let jobs = std::mem::take(&mut *self.state.jobs.lock().unwrap());
+        for job in jobs.iter().filter_map(Weak::upgrade) {
+            job.cancel();
+        }
+    }
+
+    /// Makes `job` be cancelled along with this token, or right away if it already is.
+    pub(crate) fn add_job(&self, job: Weak<dyn CancelJob>) {
+        let mut jobs = self.state.jobs.lock().unwrap();
+        if self.is_cancelled() {
+            drop(jobs);
+            if let Some(job) = job.upgrade() {
+                job.cancel();
+            }
+            return;
+        }
+        // Pruned only when the jobs would grow, so adding stays amortized O(1).
+        if jobs.len() == jobs.capacity() {
+            jobs.retain(|job| job.strong_count() > 0);
+        }
+        jobs.push(job);
+    }
+
+    /// Checks if the token has been cancelled. Long jobs should check this regularly, and return early once it is.
+    /// ```
+    /// # use gk_types_rs::job_system::cancellation::CancellationToken;
+    /// let token = CancellationToken::new();
+    /// let job_token = token.clone();
+    /// assert!(!job_token.is_cancelled());
+    /// token.cancel();
+    /// assert!(job_token.is_cancelled());
+    /// ```
+    pub fn is_cancelled(&self) -> bool {
+        return self.state.is_cancelled.load(Ordering::Acquire);
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_struct("CancellationToken").field("is_cancelled", &self.is_cancelled()).finish();
+    }
+}
+
+/// Shared by a cancellable job's container and its token. Whichever of running and cancelling takes `func` first
+/// sets the future, so cancelling a queued job leaves only this, without `func` and whatever it captured, in the queue.
+pub(crate) struct CancellableJob<T, F> {
+    func: Mutex<Option<F>>,
+    completion: WithinJobFuture<T>,
+    token: CancellationToken,
+    panic_policy: PanicPolicy
+}
+
+impl<T, F> CancellableJob<T, F>
+where T: Send + 'static, F: FnOnce(&CancellationToken) -> T + Send + 'static {
+    pub(crate) fn new(func: F, completion: WithinJobFuture<T>, token: CancellationToken, panic_policy: PanicPolicy) -> Arc<Self> {
+        let job = Arc::new(CancellableJob { func: Mutex::new(Some(func)), completion, token, panic_policy });
+        let hook: Arc<dyn CancelJob> = job.clone();
+        job.token.add_job(Arc::downgrade(&hook));
+        return job;
+    }
+
+    /// Runs `func`, unless the job was cancelled. If the token is cancelled while `func` runs, its result is discarded.
+    pub(crate) fn run(&self) {
+        let Some(func) = self.func.lock().unwrap().take() else {
+            return;
+        };
+        let result = catch_job_panic(self.panic_policy, || func(&self.token));
+        self.completion.set(match result {
+            Ok(_) if self.token.is_cancelled() => Err(JobError::Cancelled),
+            result => result
+        });
+    }
+}
+
+impl<T, F> CancelJob for CancellableJob<T, F>
+where T: Send + 'static, F: FnOnce(&CancellationToken) -> T + Send + 'static {
+    fn cancel(&self) {
+        let Some(func) = self.func.lock().unwrap().take() else {
+            return;
+        };
+        // Dropped before completing, so whatever the job captured is released without it ever running.
+        drop(func);
+        self.completion.set(Err(JobError::Cancelled));
+    }
+}
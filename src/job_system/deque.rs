@@ -0,0 +1,147 @@
+use std::ptr;
+use super::{job_container::JobContainer, sync::{fence, AtomicIsize, AtomicPtr, Mutex, Ordering}};
+
+#[cfg(not(loom))]
+const INITIAL_DEQUE_CAPACITY: usize = 256;
+// Small enough for the model checks to grow the deque.
+#[cfg(loom)]
+const INITIAL_DEQUE_CAPACITY: usize = 2;
+
+struct Buffer {
+    slots: Box<[AtomicPtr<JobContainer>]>,
+    mask: usize
+}
+
+impl Buffer {
+    fn new(capacity: usize) -> Box<Buffer> {
+        debug_assert!(capacity.is_power_of_two());
+        let slots: Vec<AtomicPtr<JobContainer>> = (0..capacity).map(|_| AtomicPtr::new(ptr::null_mut())).collect();
+        return Box::new(Buffer { slots: slots.into_boxed_slice(), mask: capacity - 1 });
+    }
+
+    fn capacity(&self) -> usize {
+        return self.slots.len();
+    }
+
+    fn get(&self, index: isize) -> *mut JobContainer {
+        return self.slots[index as usize & self.mask].load(Ordering::Relaxed);
+    }
+
+    fn put(&self, index: isize, job: *mut JobContainer) {
+        self.slots[index as usize & self.mask].store(job, Ordering::Relaxed);
+    }
+}
+
+pub(crate) enum Steal {
+    Empty,
+    Success(*mut JobContainer),
+    /// Lost a race with another thief or the owner. The deque may still have jobs.
+    Retry
+}
+
+/// Chase-Lev work stealing deque of boxed jobs. The owning worker pushes and pops at the bottom,
+/// while other workers steal from the top. Grows when full. Buffers replaced by growth are kept until
+/// the deque is dropped, since thieves may still be reading from them.
+pub(crate) struct ChaseLevDeque {
+    top: AtomicIsize,
+    bottom: AtomicIsize,
+    buffer: AtomicPtr<Buffer>,
+    // Freed on drop. Thieves may still be reading through these pointers, so the buffers must not move until then.
+    retired: Mutex<Vec<*mut Buffer>>
+}
+
+unsafe impl Send for ChaseLevDeque {}
+unsafe impl Sync for ChaseLevDeque {}
+
+impl ChaseLevDeque {
+    pub(crate) fn new() -> Self {
+        return ChaseLevDeque {
+            top: AtomicIsize::new(0),
+            bottom: AtomicIsize::new(0),
+            buffer: AtomicPtr::new(Box::into_raw(Buffer::new(INITIAL_DEQUE_CAPACITY))),
+            retired: Mutex::new(Vec::new())
+        }
+    }
+
+    /// Must only be called by the owning worker.
+    pub(crate) unsafe fn push(&self, job: *mut JobContainer) {
+        let bottom = self.bottom.load(Ordering::Relaxed);
+        let top = self.top.load(Ordering::Acquire);
+        let mut buffer = self.buffer.load(Ordering::Relaxed);
+        if bottom - top >= (*buffer).capacity() as isize {
+            buffer = self.grow(buffer, top, bottom);
+        }
+        (*buffer).put(bottom, job);
+        fence(Ordering::Release);
+        self.bottom.store(bottom + 1, Ordering::Relaxed);
+    }
+
+    /// Must only be called by the owning worker. Returns null if the deque is empty.
+    pub(crate) unsafe fn pop(&self) -> *mut JobContainer {
+        let bottom = self.bottom.load(Ordering::Relaxed) - 1;
+        let buffer = self.buffer.load(Ordering::Relaxed);
+        self.bottom.store(bottom, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        let top = self.top.load(Ordering::Relaxed);
+        if top > bottom {
+            self.bottom.store(bottom + 1, Ordering::Relaxed);
+            return ptr::null_mut();
+        }
+
+        let mut job = (*buffer).get(bottom);
+        if top == bottom {
+            // Last job, so race thieves for it.
+            if self.top.compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed).is_err() {
+                job = ptr::null_mut();
+            }
+            self.bottom.store(bottom + 1, Ordering::Relaxed);
+        }
+        return job;
+    }
+
+    /// Takes the oldest job. May be called from any thread.
+    pub(crate) fn steal(&self) -> Steal {
+        let top = self.top.load(Ordering::Acquire);
+        fence(Ordering::SeqCst);
+        let bottom = self.bottom.load(Ordering::Acquire);
+        if top >= bottom {
+            return Steal::Empty;
+        }
+
+        let buffer = self.buffer.load(Ordering::Acquire);
+        let job = unsafe { (*buffer).get(top) };
+        if self.top.compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed).is_err() {
+            return Steal::Retry;
+        }
+        return Steal::Success(job);
+    }
+
+    unsafe fn grow(&self, old_buffer: *mut Buffer, top: isize, bottom: isize) -> *mut Buffer {
+        let new_buffer = Buffer::new((*old_buffer).capacity() * 2);
+        for i in top..bottom {
+            new_buffer.put(i, (*old_buffer).get(i));
+        }
+        let new_buffer = Box::into_raw(new_buffer);
+        self.buffer.store(new_buffer, Ordering::Release);
+        self.retired.lock().unwrap().push(old_buffer);
+        return new_buffer;
+    }
+}
+
+impl Drop for ChaseLevDeque {
+    fn drop(&mut self) {
+        unsafe {
+            loop {
+                let job = self.pop();
+                if job.is_null() {
+                    break;
+                }
+                drop(Box::from_raw(job));
+            }
+            drop(Box::from_raw(self.buffer.load(Ordering::Relaxed)));
+            for buffer in self.retired.lock().unwrap().drain(..) {
+                drop(Box::from_raw(buffer));
+            }
+        }
+    }
+}
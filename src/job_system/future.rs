@@ -1,53 +1,428 @@
-use std::sync::{Mutex, Arc, TryLockError};
+use std::{any::Any, fmt, future::Future, panic::{self, AssertUnwindSafe}, pin::Pin, sync::Arc,
+    task::{Context, Poll, Waker}, time::{Duration, Instant}};
+use super::{sync::{Condvar, Mutex}, settings::PanicPolicy, system::JobSystem};
 
 struct Inner<T> {
-    data: Option<T>
+    data: Option<Result<T, JobError>>,
+    is_complete: bool,
+    // Threads sleeping on `FutureState::completed`, so that completing only notifies when someone waits.
+    waiter_count: usize,
+    // Task awaiting the future, woken once the job completes.
+    waker: Option<Waker>,
+    // Called once the job completes, to release jobs that depend on it.
+    continuations: Vec<Box<dyn FnOnce() + Send>>
+}
+
+/// Why a job did not produce a value. Returned by `JobFuture::try_wait()`.
+#[derive(Debug)]
+pub enum JobError {
+    /// The job panicked, with the payload given to `panic!()`. `JobFuture::wait()` resumes the panic with it.
+    Panicked(Box<dyn Any + Send + 'static>),
+    /// The job's `CancellationToken` was cancelled, either before the job started, or while it ran.
+    Cancelled
+}
+
+impl JobError {
+    /// Message of the panic, if it was made with a string, as `panic!()` with a message does.
+    pub fn panic_message(&self) -> Option<&str> {
+        let JobError::Panicked(payload) = self else {
+            return None;
+        };
+        return payload.downcast_ref::<&str>().copied().or_else(|| payload.downcast_ref::<String>().map(String::as_str));
+    }
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match (self, self.panic_message()) {
+            (JobError::Cancelled, _) => write!(f, "job was cancelled"),
+            (JobError::Panicked(_), Some(message)) => write!(f, "job panicked: {}", message),
+            (JobError::Panicked(_), None) => write!(f, "job panicked")
+        };
+    }
+}
+
+impl std::error::Error for JobError {}
+
+/// Runs `func`, catching a panic according to `panic_policy`.
+pub(crate) fn catch_job_panic<T, F>(panic_policy: PanicPolicy, func: F) -> Result<T, JobError>
+where F: FnOnce() -> T {
+    return match panic::catch_unwind(AssertUnwindSafe(func)) {
+        Ok(value) => Ok(value),
+        Err(payload) => match panic_policy {
+            PanicPolicy::Propagate => Err(JobError::Panicked(payload)),
+            // The panic hook has already reported the panic.
+            PanicPolicy::Abort => std::process::abort()
+        }
+    };
+}
+
+/// Something that jobs can be made to run after, such as a `JobFuture`.
+/// See `JobSystem::run_job_after()`.
+pub trait JobDependency {
+    /// Calls `continuation` once the job has completed, or right away if it already has.
+    /// Must call it exactly once, since the job system waits for every dependent job to be released.
+    /// The continuation may be called from any thread, such as the job thread completing the job.
+    fn on_complete(&self, continuation: Box<dyn FnOnce() + Send>);
+}
+
+// Shared by a `JobFuture` and the job setting it.
+struct FutureState<T> {
+    inner: Mutex<Inner<T>>,
+    // Notified once the job completes.
+    completed: Condvar
 }
 
 pub struct JobFuture<T> {
-    value: Arc<Mutex<Inner<T>>>
+    value: Arc<FutureState<T>>
 }
 
 impl<T> JobFuture<T> {
-    /// Wait for a job to finish execution, and fetch the held value.
+    /// Wait for a job to finish execution, and fetch the held value. Sleeps until the job completes.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the job's panic if it panicked, and panics if it was cancelled. Use `try_wait()` to handle either instead.
+    /// Also panics if the value was already fetched, such as by an earlier `wait()`, rather than waiting forever.
+    ///
+    /// # Examples
+    ///
     /// ```
     /// # use gk_types_rs::job_system::thread::JobThread;
     /// # use gk_types_rs::job_system::future::JobFuture;
-    /// let mut job_thread = JobThread::new();
+    /// let job_thread = JobThread::new();
     /// let future = job_thread.queue_job(|| 10);
     /// // Job will execute here
     /// job_thread.execute();
     /// let num = future.wait();
     /// assert_eq!(num, 10);
     /// ```
+    /// ``` should_panic
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let future = job_system.run_job(|| -> u32 { panic!("out of memory") });
+    /// // Will panic with "out of memory"
+    /// future.wait();
+    /// ```
+    /// ``` should_panic
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let future = job_system.run_job(|| 1);
+    /// future.wait();
+    /// // Will panic with "Job value was already fetched"
+    /// future.wait();
+    /// ```
     pub fn wait(&self) -> T {
-        loop {
-            match self.value.try_lock() {
-                Ok(mut inner) => {
-                    if inner.data.is_some() {
-                        return (*inner).data.take().unwrap();
-                    }
-                },
-                Err(e) => {
-                    if let TryLockError::Poisoned(e) = e {
-                        panic!("couldn't take job future: {}", e);
-                    }
-                }
+        return resume_panic(self.try_wait());
+    }
+
+    /// Wait for a job to finish execution, and fetch the held value, or the reason the job did not produce one.
+    /// Panics if the value was already fetched.
+    /// ```
+    /// # use gk_types_rs::job_system::{system::JobSystem, future::JobError};
+    /// let job_system = JobSystem::new_init(2);
+    /// let future = job_system.run_job(|| -> Vec<u8> { panic!("failed to decompress texture") });
+    /// match future.try_wait() {
+    ///     Ok(_) => unreachable!(),
+    ///     Err(error) => assert_eq!(error.panic_message(), Some("failed to decompress texture"))
+    /// }
+    /// ```
+    pub fn try_wait(&self) -> Result<T, JobError> {
+        let mut inner = self.value.inner.lock().unwrap();
+        inner.waiter_count += 1;
+        inner = self.value.completed.wait_while(inner, |inner| !inner.is_complete).unwrap();
+        inner.waiter_count -= 1;
+        let data = inner.data.take();
+        drop(inner);
+        // Checked without the lock, so the panic does not poison it.
+        return data.expect("Job value was already fetched");
+    }
+
+    /// Fetches the held value if the job has finished, without waiting.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the job's panic if it panicked, and panics if it was cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use gk_types_rs::job_system::thread::JobThread;
+    /// let job_thread = JobThread::new();
+    /// let future = job_thread.queue_job(|| 10);
+    /// // Not executing yet.
+    /// assert_eq!(future.try_get(), None);
+    /// job_thread.execute();
+    /// job_thread.wait();
+    /// assert_eq!(future.try_get(), Some(10));
+    /// ```
+    pub fn try_get(&self) -> Option<T> {
+        let data = self.value.inner.lock().unwrap().data.take();
+        return data.map(resume_panic);
+    }
+
+    /// Checks if the job has finished, and its value has not been fetched yet, so that fetching it will not wait.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let future = job_system.run_job(|| 5);
+    /// while !future.is_ready() {
+    ///     // Do other work for the frame.
+    /// }
+    /// assert_eq!(future.wait(), 5);
+    /// assert!(!future.is_ready());
+    /// ```
+    pub fn is_ready(&self) -> bool {
+        return self.value.inner.lock().unwrap().data.is_some();
+    }
+
+    /// Waits at most `timeout` for a job to finish execution, and fetches the held value.
+    /// Returns `None` if the job has not finished by then.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the job's panic if it panicked, and panics if it was cancelled or the value was already fetched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use std::{thread, time::Duration};
+    /// let job_system = JobSystem::new_init(2);
+    /// let path = job_system.run_job(|| { thread::sleep(Duration::from_millis(200)); vec![(0, 0), (1, 1)] });
+    /// // Gives up on the path for this frame.
+    /// assert_eq!(path.wait_timeout(Duration::from_millis(1)), None);
+    /// assert_eq!(path.wait_timeout(Duration::from_secs(10)).unwrap().len(), 2);
+    /// ```
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<T> {
+        let mut inner = self.value.inner.lock().unwrap();
+        inner.waiter_count += 1;
+        inner = self.value.completed.wait_timeout_while(inner, timeout, |inner| !inner.is_complete).unwrap().0;
+        inner.waiter_count -= 1;
+        let (is_complete, data) = (inner.is_complete, inner.data.take());
+        drop(inner);
+        // Checked and resumed without the lock, so the panic does not poison it.
+        assert!(!is_complete || data.is_some(), "Job value was already fetched");
+        return data.map(resume_panic);
+    }
+
+    /// Waits until `deadline` at the latest for a job to finish execution, and fetches the held value.
+    /// Returns `None` if the job has not finished by then. See `wait_timeout()`.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use std::time::{Duration, Instant};
+    /// let job_system = JobSystem::new_init(2);
+    /// let frame_end = Instant::now() + Duration::from_secs(10);
+    /// let future = job_system.run_job(|| 60);
+    /// assert_eq!(future.wait_deadline(frame_end), Some(60));
+    /// ```
+    pub fn wait_deadline(&self, deadline: Instant) -> Option<T> {
+        return self.wait_timeout(deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
+impl<T> JobFuture<T>
+where T: Send + 'static {
+    /// Queues a job that calls `func` with the held value once the job completes. No thread waits for it in the
+    /// meantime. If the job panicked or was cancelled, `func` is skipped and the returned future holds that instead.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the held value was already fetched, such as with `try_get()`. The same goes for the other combinators.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let heights = job_system.run_job(|| vec![3.0f32, 5.0, 1.0]);
+    /// let highest = heights.then(&job_system, |heights| heights.into_iter().fold(0.0f32, f32::max));
+    /// let label = highest.then(&job_system, |highest| format!("peak: {}", highest));
+    /// assert_eq!(label.wait(), "peak: 5");
+    /// ```
+    pub fn then<U, F>(self, job_system: &JobSystem, func: F) -> JobFuture<U>
+    where U: Send + 'static, F: FnOnce(T) -> U + Send + 'static {
+        self.value.assert_unfetched();
+        let prior = self.value.clone();
+        return job_system.run_result_job_after(&[&self], move || prior.take_data().map(func));
+    }
+
+    /// Applies `func` to the held value in a job queued on `job_system` once the job completes, for converting a value
+    /// as it flows between jobs. Scheduled the same way as `then()`, so `func` never holds up the thread that completed
+    /// the job. If the job panicked or was cancelled, `func` is skipped and the returned future holds that instead.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let path = job_system.run_job(|| vec![(0, 0), (0, 1), (1, 1)]);
+    /// let step_count = path.map(&job_system, |path| path.len() - 1);
+    /// assert_eq!(step_count.wait(), 2);
+    /// ```
+    pub fn map<U, F>(self, job_system: &JobSystem, func: F) -> JobFuture<U>
+    where U: Send + 'static, F: FnOnce(T) -> U + Send + 'static {
+        return self.then(job_system, func);
+    }
+
+    /// Combines `futures` into one that completes once all of them have, holding their values in the same order.
+    /// The values are gathered in a job queued on `job_system` once the last job completes, like `then()`.
+    /// If any job panicked, it holds the panic of the first one in `futures` instead.
+    /// ```
+    /// # use gk_types_rs::job_system::{system::JobSystem, future::JobFuture};
+    /// let job_system = JobSystem::new_init(2);
+    /// let chunks: Vec<_> = (0..4).map(|chunk| job_system.run_job(move || chunk * 16)).collect();
+    /// let offsets = JobFuture::join_all(&job_system, chunks);
+    /// assert_eq!(offsets.wait(), vec![0, 16, 32, 48]);
+    /// ```
+    pub fn join_all(job_system: &JobSystem, futures: Vec<JobFuture<T>>) -> JobFuture<Vec<T>> {
+        let states: Vec<_> = futures.iter().map(|future| { future.value.assert_unfetched(); future.value.clone() }).collect();
+        let dependencies: Vec<&dyn JobDependency> = futures.iter().map(|future| future as &dyn JobDependency).collect();
+        return job_system.run_result_job_after(&dependencies, move || states.iter().map(|state| state.take_data()).collect());
+    }
+}
+
+/// Combines two futures into one that completes once both have, holding both values.
+/// The values are paired in a job queued on `job_system` once both jobs complete, like `JobFuture::then()`.
+/// If either job panicked, it holds the panic of the first one that did, in argument order.
+/// ```
+/// # use gk_types_rs::job_system::{system::JobSystem, future};
+/// let job_system = JobSystem::new_init(2);
+/// let mesh = job_system.run_job(|| vec![0u32; 12]);
+/// let texture = job_system.run_job(|| String::from("grass.png"));
+/// let (mesh, texture) = future::join2(&job_system, mesh, texture).wait();
+/// assert_eq!((mesh.len(), texture.as_str()), (12, "grass.png"));
+/// ```
+pub fn join2<A, B>(job_system: &JobSystem, a: JobFuture<A>, b: JobFuture<B>) -> JobFuture<(A, B)>
+where A: Send + 'static, B: Send + 'static {
+    let (a_state, b_state) = (a.value.clone(), b.value.clone());
+    a_state.assert_unfetched();
+    b_state.assert_unfetched();
+    return job_system.run_result_job_after(&[&a, &b], move || a_state.take_data().and_then(|a| Ok((a, b_state.take_data()?))));
+}
+
+/// Combines three futures into one that completes once all of them have, in a job queued on `job_system`.
+/// See `join2()`.
+/// ```
+/// # use gk_types_rs::job_system::{system::JobSystem, future};
+/// let job_system = JobSystem::new_init(2);
+/// let (a, b, c) = (job_system.run_job(|| 1u8), job_system.run_job(|| 'b'), job_system.run_job(|| "c"));
+/// assert_eq!(future::join3(&job_system, a, b, c).wait(), (1, 'b', "c"));
+/// ```
+pub fn join3<A, B, C>(job_system: &JobSystem, a: JobFuture<A>, b: JobFuture<B>, c: JobFuture<C>) -> JobFuture<(A, B, C)>
+where A: Send + 'static, B: Send + 'static, C: Send + 'static {
+    let (a_state, b_state, c_state) = (a.value.clone(), b.value.clone(), c.value.clone());
+    a_state.assert_unfetched();
+    b_state.assert_unfetched();
+    c_state.assert_unfetched();
+    return job_system.run_result_job_after(&[&a, &b, &c], move || {
+        a_state.take_data().and_then(|a| Ok((a, b_state.take_data()?, c_state.take_data()?)))
+    });
+}
+
+/// Makes a future that completes as soon as any of `futures` does, holding its index and value, or its panic.
+/// The value is taken in a job queued on `job_system` once the first job completes, like `JobFuture::then()`.
+/// The values of the other jobs are dropped once they complete.
+///
+/// # Panics
+///
+/// Panics if `futures` is empty.
+///
+/// # Examples
+///
+/// ```
+/// # use gk_types_rs::job_system::{system::JobSystem, future};
+/// # use std::{thread, time::Duration};
+/// let job_system = JobSystem::new_init(2);
+/// let slow_path = job_system.run_job(|| { thread::sleep(Duration::from_millis(500)); "slow" });
+/// let fast_path = job_system.run_job(|| "fast");
+/// assert_eq!(future::when_any(&job_system, vec![slow_path, fast_path]).wait(), (1, "fast"));
+/// ```
+pub fn when_any<T>(job_system: &JobSystem, futures: Vec<JobFuture<T>>) -> JobFuture<(usize, T)>
+where T: Send + 'static {
+    assert!(!futures.is_empty(), "when_any() needs at least one future");
+    let states: Vec<_> = futures.iter().map(|future| { future.value.assert_unfetched(); future.value.clone() }).collect();
+    let dependencies: Vec<&dyn JobDependency> = futures.iter().map(|future| future as &dyn JobDependency).collect();
+    return job_system.run_result_job_after_any(&dependencies, move |index| states[index].take_data().map(|value| (index, value)));
+}
+
+impl<T> FutureState<T> {
+    // Checked before consuming a future, so that its continuation always finds the result.
+    fn assert_unfetched(&self) {
+        let inner = self.inner.lock().unwrap();
+        assert!(!inner.is_complete || inner.data.is_some(), "Job value was already fetched");
+    }
+
+    // Takes the result of the completed job, for continuations.
+    fn take_data(&self) -> Result<T, JobError> {
+        return self.inner.lock().unwrap().data.take().expect("Job value was already fetched");
+    }
+}
+
+// Gives the value of a finished job, or resumes its panic.
+fn resume_panic<T>(result: Result<T, JobError>) -> T {
+    return match result {
+        Ok(value) => value,
+        Err(JobError::Panicked(payload)) => panic::resume_unwind(payload),
+        Err(JobError::Cancelled) => panic!("Job was cancelled")
+    };
+}
+
+/// Awaiting a `JobFuture` gives the job's value without blocking. Like `wait()`, it resumes the job's panic if it
+/// panicked, and panics if it was cancelled.
+/// The task is woken from the thread completing the job.
+/// ```
+/// # use gk_types_rs::job_system::system::JobSystem;
+/// let job_system = JobSystem::new_init(2);
+/// let mesh = job_system.run_job(|| vec![0.0f32; 300]);
+/// let vertex_count = job_system.spawn_async(async move { mesh.await.len() / 3 });
+/// assert_eq!(vertex_count.wait(), 100);
+/// ```
+impl<T> Future for JobFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<T> {
+        let mut inner = self.value.inner.lock().unwrap();
+        if let Some(data) = inner.data.take() {
+            drop(inner);
+            // Resumed without the lock, so the panic does not poison it.
+            return Poll::Ready(resume_panic(data));
+        }
+        if inner.is_complete {
+            drop(inner);
+            panic!("Job value was already fetched");
+        }
+        match &mut inner.waker {
+            Some(waker) if waker.will_wake(context.waker()) => {},
+            waker => *waker = Some(context.waker().clone())
+        }
+        return Poll::Pending;
+    }
+}
+
+impl<T> JobDependency for JobFuture<T> {
+    fn on_complete(&self, continuation: Box<dyn FnOnce() + Send>) {
+        {
+            let mut inner = self.value.inner.lock().unwrap();
+            if !inner.is_complete {
+                inner.continuations.push(continuation);
+                return;
             }
-            std::thread::yield_now();
         }
+        continuation();
     }
 }
 
 
 pub(crate) struct WithinJobFuture<T> {
-    value: Arc<Mutex<Inner<T>>>,
+    value: Arc<FutureState<T>>,
 }
 
 impl<T> WithinJobFuture<T> {
     pub(crate) fn new() -> (JobFuture<T>, WithinJobFuture<T>) {
         let wait_job_future = JobFuture {
-            value: Arc::new(Mutex::new(Inner { data: None }))};
+            value: Arc::new(FutureState {
+                inner: Mutex::new(Inner { data: None, is_complete: false, waiter_count: 0, waker: None, continuations: Vec::new() }),
+                completed: Condvar::new()
+            })
+        };
 
         let within_job_future = WithinJobFuture {
             value: wait_job_future.value.clone(),
@@ -56,8 +431,22 @@ impl<T> WithinJobFuture<T> {
         return (wait_job_future, within_job_future);
     }
 
-    pub(crate) fn set(&self, data: T) {
-        let mut inner = self.value.lock().unwrap();
-        (*inner).data = Some(data);
+    pub(crate) fn set(&self, data: Result<T, JobError>) {
+        let (waker, continuations) = {
+            let mut inner = self.value.inner.lock().unwrap();
+            inner.data = Some(data);
+            inner.is_complete = true;
+            if inner.waiter_count > 0 {
+                self.value.completed.notify_all();
+            }
+            (inner.waker.take(), std::mem::take(&mut inner.continuations))
+        };
+        // Called without the lock, since wakers and continuations may queue jobs or check this future.
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        for continuation in continuations {
+            continuation();
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,219 @@
+use std::sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex};
+use super::{future::{JobFuture, JobError, WithinJobFuture, catch_job_panic}, job_container::JobContainer, settings::PanicPolicy};
+
+/// Queues a released job. Given by the `JobSystem` running the dependent jobs, and called from whichever thread
+/// completes the last prerequisite.
+pub(crate) type ReleaseJob = Arc<dyn Fn(JobContainer) + Send + Sync>;
+
+/// A job waiting on its prerequisites, which is released once the last one completes.
+pub(crate) struct DependentJob {
+    remaining_prerequisites: AtomicUsize,
+    job: Mutex<Option<JobContainer>>,
+    release: ReleaseJob
+}
+
+impl DependentJob {
+    /// The job is held back until `complete_prerequisite()` has been called `prerequisite_count` times.
+    pub(crate) fn new(job: JobContainer, prerequisite_count: usize, release: ReleaseJob) -> Arc<DependentJob> {
+        return Arc::new(DependentJob {
+            remaining_prerequisites: AtomicUsize::new(prerequisite_count),
+            job: Mutex::new(Some(job)),
+            release
+        });
+    }
+
+    pub(crate) fn complete_prerequisite(&self) {
+        if self.remaining_prerequisites.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        let job = self.job.lock().unwrap().take().expect("Dependent job was already released");
+        (self.release)(job);
+    }
+}
+
+/// Handle to a job added to a `JobGraphBuilder`, used to make later jobs depend on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobGraphNode(usize);
+
+struct GraphJob {
+    // Only locked by the single job running this node during a run, and runs never overlap, so it is never contended.
+    func: Mutex<Box<dyn FnMut() + Send>>,
+    dependents: Box<[usize]>,
+    prerequisite_count: usize
+}
+
+/// Builds a `JobGraph`, by adding jobs along with the jobs they must run after.
+/// Jobs can only depend on jobs added before them, so the graph can never have cycles.
+/// ```
+/// # use gk_types_rs::job_system::graph::JobGraphBuilder;
+/// let mut builder = JobGraphBuilder::new();
+/// let a = builder.add_job(|| println!("a"));
+/// let b = builder.add_job(|| println!("b"));
+/// // Runs once both a and b have finished.
+/// builder.add_job_after(&[a, b], || println!("c"));
+/// let graph = builder.build();
+/// assert_eq!(graph.len(), 3);
+/// ```
+pub struct JobGraphBuilder {
+    funcs: Vec<Box<dyn FnMut() + Send>>,
+    prerequisites: Vec<Vec<usize>>
+}
+
+impl JobGraphBuilder {
+    pub fn new() -> Self {
+        return JobGraphBuilder { funcs: Vec::new(), prerequisites: Vec::new() };
+    }
+
+    /// Adds a job with no prerequisites, which starts as soon as the graph runs.
+    pub fn add_job<F>(&mut self, func: F) -> JobGraphNode
+    where F: FnMut() + Send + 'static {
+        return self.add_job_after(&[], func);
+    }
+
+    /// Adds a job that runs only once every job in `prerequisites` has finished.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a prerequisite was not added to this builder.
+    pub fn add_job_after<F>(&mut self, prerequisites: &[JobGraphNode], func: F) -> JobGraphNode
+    where F: FnMut() + Send + 'static {
+        let index = self.funcs.len();
+        let mut node_prerequisites: Vec<usize> = Vec::with_capacity(prerequisites.len());
+        for prerequisite in prerequisites {
+            assert!(prerequisite.0 < index, "Job graph prerequisite was not added to this builder");
+            if !node_prerequisites.contains(&prerequisite.0) {
+                node_prerequisites.push(prerequisite.0);
+            }
+        }
+        self.funcs.push(Box::new(func));
+        self.prerequisites.push(node_prerequisites);
+        return JobGraphNode(index);
+    }
+
+    /// Compiles the jobs into a `JobGraph`, which can be run any number of times.
+    pub fn build(self) -> JobGraph {
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); self.funcs.len()];
+        for (index, node_prerequisites) in self.prerequisites.iter().enumerate() {
+            for &prerequisite in node_prerequisites {
+                dependents[prerequisite].push(index);
+            }
+        }
+
+        let roots: Vec<usize> = (0..self.funcs.len()).filter(|&index| self.prerequisites[index].is_empty()).collect();
+        let jobs: Vec<GraphJob> = self.funcs.into_iter().zip(dependents).zip(self.prerequisites)
+            .map(|((func, node_dependents), node_prerequisites)| GraphJob {
+                func: Mutex::new(func),
+                dependents: node_dependents.into_boxed_slice(),
+                prerequisite_count: node_prerequisites.len()
+            }).collect();
+
+        return JobGraph {
+            jobs: jobs.into(),
+            roots: roots.into_boxed_slice(),
+            is_running: Arc::new(AtomicBool::new(false))
+        };
+    }
+}
+
+impl Default for JobGraphBuilder {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/// A compiled set of jobs and their dependencies, made with `JobGraphBuilder`.
+/// Run it with `JobSystem::run_graph()`, which queues each job once all of its prerequisites have finished.
+/// A graph can be run again, such as once per frame, but only once the previous run has completed.
+pub struct JobGraph {
+    jobs: Arc<[GraphJob]>,
+    roots: Box<[usize]>,
+    is_running: Arc<AtomicBool>
+}
+
+impl JobGraph {
+    /// Number of jobs in the graph.
+    pub fn len(&self) -> usize {
+        return self.jobs.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.jobs.is_empty();
+    }
+
+    /// Checks if a run of this graph has not completed yet.
+    pub fn is_running(&self) -> bool {
+        return self.is_running.load(Ordering::Acquire);
+    }
+
+    /// Releases the jobs without prerequisites through `release`. Each job releases its dependents as it finishes,
+    /// and the returned future completes once every job has. Panics are handled according to `panic_policy`.
+    pub(crate) fn start(&self, release: ReleaseJob, panic_policy: PanicPolicy) -> JobFuture<()> {
+        assert!(!self.is_running.swap(true, Ordering::AcqRel), "JobGraph is already running");
+        let (future, completion) = WithinJobFuture::<()>::new();
+        if self.jobs.is_empty() {
+            self.is_running.store(false, Ordering::Release);
+            completion.set(Ok(()));
+            return future;
+        }
+
+        let run = Arc::new(GraphRun {
+            jobs: self.jobs.clone(),
+            remaining_prerequisites: self.jobs.iter().map(|job| AtomicUsize::new(job.prerequisite_count)).collect(),
+            remaining_jobs: AtomicUsize::new(self.jobs.len()),
+            is_running: self.is_running.clone(),
+            completion,
+            release,
+            panic_policy,
+            panic: Mutex::new(None)
+        });
+        for &root in self.roots.iter() {
+            (run.release)(GraphRun::make_job(&run, root));
+        }
+        return future;
+    }
+}
+
+// State of a single run of a `JobGraph`.
+struct GraphRun {
+    jobs: Arc<[GraphJob]>,
+    remaining_prerequisites: Box<[AtomicUsize]>,
+    remaining_jobs: AtomicUsize,
+    is_running: Arc<AtomicBool>,
+    completion: WithinJobFuture<()>,
+    release: ReleaseJob,
+    panic_policy: PanicPolicy,
+    // First panic of the run, which the completion future is set to.
+    panic: Mutex<Option<JobError>>
+}
+
+impl GraphRun {
+    fn make_job(run: &Arc<GraphRun>, index: usize) -> JobContainer {
+        let run = run.clone();
+        return JobContainer::new(move || GraphRun::run_job(&run, index));
+    }
+
+    fn run_job(run: &Arc<GraphRun>, index: usize) {
+        let job = &run.jobs[index];
+        // Once a job has panicked, the jobs that have not run yet are skipped, but still released so the run completes.
+        if run.panic.lock().unwrap().is_none() {
+            let mut func = job.func.lock().unwrap();
+            if let Err(error) = catch_job_panic(run.panic_policy, &mut *func) {
+                run.panic.lock().unwrap().get_or_insert(error);
+            }
+        }
+
+        for &dependent in job.dependents.iter() {
+            if run.remaining_prerequisites[dependent].fetch_sub(1, Ordering::AcqRel) == 1 {
+                (run.release)(GraphRun::make_job(run, dependent));
+            }
+        }
+        if run.remaining_jobs.fetch_sub(1, Ordering::AcqRel) == 1 {
+            run.is_running.store(false, Ordering::Release);
+            let result = match run.panic.lock().unwrap().take() {
+                Some(error) => Err(error),
+                None => Ok(())
+            };
+            run.completion.set(result);
+        }
+    }
+}
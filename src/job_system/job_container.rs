@@ -434,12 +434,12 @@ impl Default for Job {
 
 
 pub(crate) struct JobContainer {
-    func: Option<Box<dyn FnMut()>>
+    func: Option<Box<dyn FnMut() + Send>>
 }
 
 impl JobContainer {
     pub(crate) fn new<F>(func: F) -> Self
-    where F: FnMut() + 'static {
+    where F: FnMut() + Send + 'static {
         return JobContainer { func: Some(Box::new(func)) }
     }
 
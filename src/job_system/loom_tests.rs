@@ -0,0 +1,143 @@
+//! Model checks of the lock-free job queues, the job thread's sleep and wake protocol, and `JobFuture` completion,
+//! which explore every interleaving of the threads touching them.
+//! Run with `RUSTFLAGS="--cfg loom" cargo test --release --lib loom`.
+//!
+//! Scope completion, the work stealing pool's sleep protocol and `JobSystem::wait()` still use std primitives,
+//! so they are not covered here.
+
+use std::collections::VecDeque;
+use loom::{model::Builder, sync::{Arc, atomic::{AtomicUsize, Ordering}}, thread};
+use super::{job_container::JobContainer, mpsc_queue::MpscJobQueue, deque::{ChaseLevDeque, Steal}, future::WithinJobFuture,
+    thread::JobThread, settings::JobSystemSettings};
+
+fn counting_job(ran: &Arc<AtomicUsize>) -> JobContainer {
+    let ran = ran.clone();
+    return JobContainer::new(move || { ran.fetch_add(1, Ordering::Relaxed); });
+}
+
+fn run_all(work: VecDeque<JobContainer>) {
+    for mut job in work {
+        job.invoke();
+    }
+}
+
+#[test]
+fn loom_mpsc_queue_hands_every_job_to_the_consumer_once() {
+    loom::model(|| {
+        let queue = Arc::new(MpscJobQueue::new(2));
+        let ran = Arc::new(AtomicUsize::new(0));
+        let producers: Vec<_> = (0..2).map(|_| {
+            let (queue, job) = (queue.clone(), counting_job(&ran));
+            thread::spawn(move || assert!(queue.try_push(job).is_ok()))
+        }).collect();
+
+        let mut work = VecDeque::new();
+        unsafe { queue.collect_into(&mut work) };
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        unsafe { queue.collect_into(&mut work) };
+        assert_eq!(work.len(), 2);
+        run_all(work);
+        assert_eq!(ran.load(Ordering::Relaxed), 2);
+    });
+}
+
+#[test]
+fn loom_mpsc_queue_overflow_keeps_every_job() {
+    // Four threads take too long to check exhaustively, and bugs rarely need more preemptions than this to show.
+    let mut builder = Builder::new();
+    builder.preemption_bound = Some(3);
+    builder.check(|| {
+        // Smallest ring there is, so the third job overflows whenever the consumer has not taken one yet.
+        let queue = Arc::new(MpscJobQueue::new(1));
+        let ran = Arc::new(AtomicUsize::new(0));
+        // Pushes the same way as `JobThread` does with `QueueFullPolicy::Grow`.
+        let producers: Vec<_> = (0..3).map(|_| {
+            let (queue, job) = (queue.clone(), counting_job(&ran));
+            thread::spawn(move || {
+                let refused_job = match queue.has_overflowed() {
+                    false => queue.try_push(job).err(),
+                    true => Some(job)
+                };
+                if let Some(job) = refused_job {
+                    queue.push_overflow(job);
+                }
+            })
+        }).collect();
+
+        let mut work = VecDeque::new();
+        unsafe { queue.collect_into(&mut work) };
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        unsafe { queue.collect_into(&mut work) };
+        assert_eq!(work.len(), 3);
+        run_all(work);
+        assert_eq!(ran.load(Ordering::Relaxed), 3);
+    });
+}
+
+#[test]
+fn loom_deque_jobs_are_taken_once_by_owner_or_thief() {
+    loom::model(|| {
+        let deque = Arc::new(ChaseLevDeque::new());
+        let ran = Arc::new(AtomicUsize::new(0));
+        let thief = {
+            let deque = deque.clone();
+            thread::spawn(move || {
+                if let Steal::Success(job) = deque.steal() {
+                    unsafe { Box::from_raw(job) }.invoke();
+                }
+            })
+        };
+
+        // One more job than the initial capacity, so the deque grows while the thief may be reading it.
+        for _ in 0..3 {
+            unsafe { deque.push(Box::into_raw(Box::new(counting_job(&ran)))) };
+        }
+        loop {
+            let job = unsafe { deque.pop() };
+            if job.is_null() {
+                break;
+            }
+            unsafe { Box::from_raw(job) }.invoke();
+        }
+        thief.join().unwrap();
+        assert_eq!(ran.load(Ordering::Relaxed), 3);
+    });
+}
+
+#[test]
+fn loom_job_future_wakes_the_waiting_thread() {
+    loom::model(|| {
+        let (future, completion) = WithinJobFuture::new();
+        let job = thread::spawn(move || completion.set(Ok(5)));
+        assert_eq!(future.wait(), 5);
+        job.join().unwrap();
+    });
+}
+
+#[test]
+fn loom_job_thread_never_sleeps_through_a_queued_job() {
+    // Bounded like the overflow model, since the job thread, its queues and the kill on drop make for many states.
+    let mut builder = Builder::new();
+    builder.preemption_bound = Some(2);
+    builder.check(|| {
+        let job_thread = JobThread::new_with_settings(JobSystemSettings { queue_capacity: 2, ..Default::default() });
+        let ran = Arc::new(AtomicUsize::new(0));
+        let first_ran = ran.clone();
+        let first = job_thread.queue_job(move || first_ran.fetch_add(1, Ordering::Relaxed));
+        job_thread.execute();
+        first.wait();
+
+        // Queued while the job thread may be going back to sleep.
+        let second_ran = ran.clone();
+        job_thread.queue_job(move || second_ran.fetch_add(1, Ordering::Relaxed));
+        job_thread.execute();
+        job_thread.wait();
+        assert_eq!(ran.load(Ordering::Relaxed), 2);
+        // Wakes the job thread to exit, and joins it.
+        drop(job_thread);
+    });
+}
@@ -1,7 +1,31 @@
 mod ring_queue;
+// Only the loom models use it when the mutex queue is swapped in.
+#[cfg_attr(feature = "mutex_job_queue", allow(dead_code))]
+mod mpsc_queue;
+#[cfg(feature = "mutex_job_queue")]
+mod mutex_queue;
 mod active_jobs;
 mod job_container;
+mod deque;
+mod work_stealing;
+mod sync;
+mod async_task;
+#[cfg(all(test, loom))]
+mod loom_tests;
+
+// Queues of the job threads. The `mutex_job_queue` feature swaps in the mutex protected queue they replaced,
+// to benchmark against.
+#[cfg(not(feature = "mutex_job_queue"))]
+use mpsc_queue::MpscJobQueue as JobQueue;
+#[cfg(feature = "mutex_job_queue")]
+use mutex_queue::MutexJobQueue as JobQueue;
 
 pub mod thread;
 pub mod system;
-pub mod future;
\ No newline at end of file
+pub mod future;
+pub mod settings;
+pub mod graph;
+pub mod priority;
+pub mod parallel;
+pub mod scope;
+pub mod cancellation;
\ No newline at end of file
@@ -0,0 +1,142 @@
+use std::{collections::VecDeque, mem::MaybeUninit};
+use super::{job_container::JobContainer, ring_queue::JobRingQueue, sync::{AtomicUsize, Mutex, Ordering, UnsafeCell}};
+
+// Keeps the producer and consumer positions on separate cache lines.
+#[repr(align(64))]
+struct CacheAligned<T>(T);
+
+struct Slot {
+    // Equal to the position when the slot is free to write, and to position + 1 when it holds a job.
+    sequence: AtomicUsize,
+    job: UnsafeCell<MaybeUninit<JobContainer>>
+}
+
+/// Lock-free bounded ring of jobs, with many producing threads and a single consuming job thread.
+/// Based on Dmitry Vyukov's bounded MPMC queue, with the consumer side simplified since only one thread takes jobs.
+/// Jobs that do not fit in the ring can go to a mutex protected overflow queue, which is only touched when the ring is full,
+/// and only allocated the first time it is.
+pub(crate) struct MpscJobQueue {
+    slots: Box<[Slot]>,
+    mask: usize,
+    enqueue_position: CacheAligned<AtomicUsize>,
+    dequeue_position: CacheAligned<AtomicUsize>,
+    overflow: Mutex<Option<JobRingQueue>>,
+    overflow_count: AtomicUsize
+}
+
+unsafe impl Send for MpscJobQueue {}
+unsafe impl Sync for MpscJobQueue {}
+
+impl MpscJobQueue {
+    /// Capacity is rounded up to the next power of two, and to at least 2.
+    pub(crate) fn new(capacity: usize) -> Self {
+        debug_assert_ne!(capacity, 0, "Cannot create a job queue with 0 capacity");
+        // With a single slot, a job waiting at one position looks the same as a free slot at the next position,
+        // so a second producer would overwrite it.
+        let capacity = capacity.next_power_of_two().max(2);
+        let slots: Vec<Slot> = (0..capacity).map(|i| Slot {
+            sequence: AtomicUsize::new(i),
+            job: UnsafeCell::new(MaybeUninit::uninit())
+        }).collect();
+        return MpscJobQueue {
+            slots: slots.into_boxed_slice(),
+            mask: capacity - 1,
+            enqueue_position: CacheAligned(AtomicUsize::new(0)),
+            dequeue_position: CacheAligned(AtomicUsize::new(0)),
+            overflow: Mutex::new(None),
+            overflow_count: AtomicUsize::new(0)
+        }
+    }
+
+    /// Capacity of the lock-free ring, not counting the overflow queue.
+    pub(crate) fn capacity(&self) -> usize {
+        return self.slots.len();
+    }
+
+    /// Whether `collect_into()` would take any jobs right now. This does not count
+    /// a slot a producer has claimed but not written yet, so the consumer never waits on that producer.
+    /// Must only be called by the consuming job thread.
+    pub(crate) fn has_collectable_jobs(&self) -> bool {
+        let position = self.dequeue_position.0.load(Ordering::Relaxed);
+        let slot = &self.slots[position & self.mask];
+        return slot.sequence.load(Ordering::Acquire) == position + 1 || self.has_overflowed();
+    }
+
+    pub(crate) fn has_overflowed(&self) -> bool {
+        return self.overflow_count.load(Ordering::Acquire) > 0;
+    }
+
+    /// Pushes onto the ring without locking, giving the job back if the ring is full.
+    pub(crate) fn try_push(&self, job: JobContainer) -> Result<(), JobContainer> {
+        let mut position = self.enqueue_position.0.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position & self.mask];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let difference = sequence as isize - position as isize;
+            if difference == 0 {
+                match self.enqueue_position.0.compare_exchange_weak(position, position + 1, Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        slot.job.with_mut(|slot_job| unsafe { (*slot_job).write(job) });
+                        slot.sequence.store(position + 1, Ordering::Release);
+                        return Ok(());
+                    },
+                    Err(current) => position = current
+                }
+            } else if difference < 0 {
+                // The consumer has not taken the job a full lap behind, so the ring is full.
+                return Err(job);
+            } else {
+                position = self.enqueue_position.0.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pushes onto the overflow queue, growing it as needed. Used once the ring is full and the queue may grow.
+    /// While any jobs are overflowed, producers should keep pushing here so that jobs stay in order.
+    pub(crate) fn push_overflow(&self, job: JobContainer) {
+        let mut overflow_lock = self.overflow.lock().unwrap();
+        let overflow = (*overflow_lock).get_or_insert_with(|| JobRingQueue::new(self.capacity()));
+        if overflow.is_full() {
+            overflow.grow();
+        }
+        overflow.push(job);
+        self.overflow_count.fetch_add(1, Ordering::Release);
+    }
+
+    /// Moves every available job into `work` in one batch, returning how many were moved.
+    /// Must only be called by the consuming job thread.
+    pub(crate) unsafe fn collect_into(&self, work: &mut VecDeque<JobContainer>) -> usize {
+        let start_len = work.len();
+        let mut position = self.dequeue_position.0.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position & self.mask];
+            if slot.sequence.load(Ordering::Acquire) != position + 1 {
+                break;
+            }
+            work.push_back(slot.job.with_mut(|slot_job| (*slot_job).assume_init_read()));
+            slot.sequence.store(position + self.capacity(), Ordering::Release);
+            position += 1;
+        }
+        self.dequeue_position.0.store(position, Ordering::Release);
+
+        if self.has_overflowed() {
+            let mut overflow_lock = self.overflow.lock().unwrap();
+            // Kept once allocated, since a queue that overflowed once is likely to again.
+            if let Some(overflow) = (*overflow_lock).as_mut() {
+                work.reserve(overflow.len());
+                while let Some(job) = overflow.pop() {
+                    work.push_back(job);
+                }
+            }
+            self.overflow_count.store(0, Ordering::Release);
+        }
+        return work.len() - start_len;
+    }
+}
+
+impl Drop for MpscJobQueue {
+    fn drop(&mut self) {
+        let mut remaining = VecDeque::new();
+        unsafe { self.collect_into(&mut remaining) };
+    }
+}
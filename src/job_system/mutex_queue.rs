@@ -0,0 +1,57 @@
+use std::collections::VecDeque;
+use super::{job_container::JobContainer, ring_queue::JobRingQueue, sync::Mutex};
+
+/// Job queue behind a single mutex, as job threads used before `MpscJobQueue`. Has the same interface, so the
+/// `mutex_job_queue` feature can swap it back in to benchmark the lock-free queue against, such as with `benches/job_spam.rs`.
+pub(crate) struct MutexJobQueue {
+    queue: Mutex<JobRingQueue>,
+    capacity: usize
+}
+
+impl MutexJobQueue {
+    pub(crate) fn new(capacity: usize) -> Self {
+        return MutexJobQueue { queue: Mutex::new(JobRingQueue::new(capacity)), capacity };
+    }
+
+    /// Initial capacity, before the queue grows.
+    pub(crate) fn capacity(&self) -> usize {
+        return self.capacity;
+    }
+
+    pub(crate) fn has_collectable_jobs(&self) -> bool {
+        return !self.queue.lock().unwrap().is_empty();
+    }
+
+    /// Full queues grow in place, so jobs never go anywhere else.
+    pub(crate) fn has_overflowed(&self) -> bool {
+        return false;
+    }
+
+    pub(crate) fn try_push(&self, job: JobContainer) -> Result<(), JobContainer> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.is_full() {
+            return Err(job);
+        }
+        queue.push(job);
+        return Ok(());
+    }
+
+    pub(crate) fn push_overflow(&self, job: JobContainer) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.is_full() {
+            queue.grow();
+        }
+        queue.push(job);
+    }
+
+    /// Safe to call from any thread, but unsafe to match `MpscJobQueue::collect_into()`.
+    pub(crate) unsafe fn collect_into(&self, work: &mut VecDeque<JobContainer>) -> usize {
+        let mut queue = self.queue.lock().unwrap();
+        let collected_count = queue.len();
+        work.reserve(collected_count);
+        while let Some(job) = queue.pop() {
+            work.push_back(job);
+        }
+        return collected_count;
+    }
+}
@@ -0,0 +1,182 @@
+use std::{any::Any, ops::Range, panic::{self, AssertUnwindSafe}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Condvar, Mutex}};
+use super::{system::JobSystem, job_container::JobContainer, priority::JobPriority};
+
+/// Number of chunks per job thread when work is split without a grain size, so that uneven chunks still balance out.
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// Splits `range` into chunks of `grain` indices, and calls `func` for every index on the job threads and the calling
+/// thread, returning once every index is done. A `grain` of 0 picks one from the number of job threads.
+/// If `func` panics, the chunks that have not started are skipped, and the panic is resumed on the calling thread.
+/// See `JobSystem::parallel_for()`.
+pub(crate) fn parallel_for<F>(job_system: &JobSystem, range: Range<usize>, grain: usize, func: &F)
+where F: Fn(usize) + Sync {
+    let length = range.end.saturating_sub(range.start);
+    if length == 0 {
+        return;
+    }
+    let grain = if grain == 0 {
+        (length / (job_system.thread_count() * CHUNKS_PER_THREAD)).max(1)
+    } else {
+        grain
+    };
+
+    let state = Arc::new(ParallelFor {
+        start: range.start,
+        end: range.end,
+        grain,
+        chunk_count: length.div_ceil(grain),
+        next_chunk: AtomicUsize::new(0),
+        completed_chunks: AtomicUsize::new(0),
+        all_completed: (Mutex::new(()), Condvar::new()),
+        has_panicked: AtomicBool::new(false),
+        panic: Mutex::new(None),
+        func: func as *const F as *const (),
+        call: call_func::<F>
+    });
+
+    let helper_count = (state.chunk_count - 1).min(job_system.thread_count());
+    for _ in 0..helper_count {
+        let state = state.clone();
+        // A refused helper is fine, since the calling thread runs whatever chunks are left.
+        let _ = job_system.try_push_job(JobContainer::new(move || state.run_chunks()), JobPriority::Normal);
+    }
+    // The calling thread helps instead of only waiting, so this cannot deadlock when called from within a job.
+    state.run_chunks();
+    // Every chunk is claimed by now, so this only sleeps until the helpers finish the chunks they are running.
+    let (lock, cvar) = &state.all_completed;
+    drop(cvar.wait_while(lock.lock().unwrap(), |_| state.completed_chunks.load(Ordering::Acquire) < state.chunk_count).unwrap());
+    let panic = state.panic.lock().unwrap().take();
+    if let Some(payload) = panic {
+        panic::resume_unwind(payload);
+    }
+}
+
+unsafe fn call_func<F>(func: *const (), index: usize)
+where F: Fn(usize) + Sync {
+    (*(func as *const F))(index);
+}
+
+// Shared by the calling thread and the helper jobs of a `parallel_for()` call, which claim chunks one at a time.
+struct ParallelFor {
+    start: usize,
+    end: usize,
+    grain: usize,
+    chunk_count: usize,
+    next_chunk: AtomicUsize,
+    completed_chunks: AtomicUsize,
+    // Notified once `completed_chunks` reaches `chunk_count`.
+    all_completed: (Mutex<()>, Condvar),
+    has_panicked: AtomicBool,
+    // First panic of `func`, resumed by the calling thread.
+    panic: Mutex<Option<Box<dyn Any + Send + 'static>>>,
+    // Borrowed from the `parallel_for()` call. Only used after claiming a chunk, and the call does not return
+    // until every claimed chunk has completed, so helper jobs that start late never touch it.
+    func: *const (),
+    call: unsafe fn(*const (), usize)
+}
+
+// `func` is `Sync`, so calling it from any thread is fine.
+unsafe impl Send for ParallelFor {}
+unsafe impl Sync for ParallelFor {}
+
+impl ParallelFor {
+    fn run_chunks(&self) {
+        loop {
+            let chunk = self.next_chunk.fetch_add(1, Ordering::AcqRel);
+            if chunk >= self.chunk_count {
+                return;
+            }
+            if !self.has_panicked.load(Ordering::Acquire) {
+                let chunk_start = self.start + chunk * self.grain;
+                // Saturating, since a large grain or a range ending near `usize::MAX` would overflow.
+                let chunk_end = chunk_start.saturating_add(self.grain).min(self.end);
+                let result = panic::catch_unwind(AssertUnwindSafe(|| for index in chunk_start..chunk_end {
+                    unsafe { (self.call)(self.func, index) };
+                }));
+                if let Err(payload) = result {
+                    self.panic.lock().unwrap().get_or_insert(payload);
+                    self.has_panicked.store(true, Ordering::Release);
+                }
+            }
+            if self.completed_chunks.fetch_add(1, Ordering::AcqRel) + 1 == self.chunk_count {
+                let _lock = self.all_completed.0.lock().unwrap();
+                self.all_completed.1.notify_all();
+            }
+        }
+    }
+}
+
+/// Parallel iterator over shared references to the elements of a slice, run on a `JobSystem`.
+/// Made with `ArrayList::par_iter()` or `ParIter::new()`.
+/// ```
+/// # use gk_types_rs::job_system::{system::JobSystem, parallel::ParIter};
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// let job_system = JobSystem::new_init(2);
+/// let numbers: Vec<usize> = (0..1000).collect();
+/// let sum = AtomicUsize::new(0);
+/// ParIter::new(&job_system, &numbers).with_grain(100).for_each(|number| { sum.fetch_add(*number, Ordering::Relaxed); });
+/// assert_eq!(sum.load(Ordering::Relaxed), 499500);
+/// ```
+pub struct ParIter<'a, T> {
+    job_system: &'a JobSystem,
+    items: &'a [T],
+    grain: usize
+}
+
+impl<'a, T> ParIter<'a, T>
+where T: Sync {
+    pub fn new(job_system: &'a JobSystem, items: &'a [T]) -> Self {
+        return ParIter { job_system, items, grain: 0 };
+    }
+
+    /// Sets how many elements each chunk holds. By default, it is picked from the number of job threads.
+    pub fn with_grain(mut self, grain: usize) -> Self {
+        self.grain = grain;
+        return self;
+    }
+
+    /// Calls `func` on every element across the job threads, returning once all are done.
+    pub fn for_each<F>(self, func: F)
+    where F: Fn(&T) + Sync {
+        let items = self.items;
+        parallel_for(self.job_system, 0..items.len(), self.grain, &|index| func(&items[index]));
+    }
+}
+
+/// Parallel iterator over mutable references to the elements of a slice, run on a `JobSystem`.
+/// Made with `ArrayList::par_iter_mut()` or `ParIterMut::new()`.
+/// ```
+/// # use gk_types_rs::job_system::{system::JobSystem, parallel::ParIterMut};
+/// let job_system = JobSystem::new_init(2);
+/// let mut numbers: Vec<usize> = (0..1000).collect();
+/// ParIterMut::new(&job_system, &mut numbers).for_each(|number| *number *= 2);
+/// assert_eq!(numbers[999], 1998);
+/// ```
+pub struct ParIterMut<'a, T> {
+    job_system: &'a JobSystem,
+    items: &'a mut [T],
+    grain: usize
+}
+
+impl<'a, T> ParIterMut<'a, T>
+where T: Send {
+    pub fn new(job_system: &'a JobSystem, items: &'a mut [T]) -> Self {
+        return ParIterMut { job_system, items, grain: 0 };
+    }
+
+    /// Sets how many elements each chunk holds. By default, it is picked from the number of job threads.
+    pub fn with_grain(mut self, grain: usize) -> Self {
+        self.grain = grain;
+        return self;
+    }
+
+    /// Calls `func` on every element across the job threads, returning once all are done.
+    pub fn for_each<F>(self, func: F)
+    where F: Fn(&mut T) + Sync {
+        // Each index is visited exactly once, so the mutable references never overlap.
+        let items_address = self.items.as_mut_ptr() as usize;
+        parallel_for(self.job_system, 0..self.items.len(), self.grain, &|index| {
+            func(unsafe { &mut *(items_address as *mut T).add(index) })
+        });
+    }
+}
@@ -0,0 +1,71 @@
+/// How urgently a job should run. Each priority has its own queue, and job threads always take the most urgent
+/// queued job, except to keep less urgent jobs from starving. See `JobSystemSettings::starvation_limit`.
+/// ```
+/// # use gk_types_rs::job_system::{thread::JobThread, priority::JobPriority, settings::JobSystemSettings};
+/// # use std::sync::{Arc, Mutex};
+/// let job_thread = JobThread::new_with_settings(JobSystemSettings { starvation_limit: 2, ..Default::default() });
+/// let order = Arc::new(Mutex::new(Vec::new()));
+/// let order_background = order.clone();
+/// job_thread.queue_job_with_priority(JobPriority::Background, move || order_background.lock().unwrap().push("background"));
+/// for _ in 0..5 {
+///     let order_critical = order.clone();
+///     job_thread.queue_job_with_priority(JobPriority::Critical, move || order_critical.lock().unwrap().push("critical"));
+/// }
+/// job_thread.execute();
+/// job_thread.wait();
+/// // Two critical jobs go first, then the background job has waited long enough.
+/// assert_eq!(order.lock().unwrap()[2], "background");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum JobPriority {
+    /// Must finish this frame, such as jobs the main thread is about to wait on.
+    Critical,
+    /// Should run ahead of normal gameplay jobs.
+    High,
+    /// Priority of jobs run without one, such as through `JobSystem::run_job()`.
+    #[default]
+    Normal,
+    /// Can take many frames, such as asset decoding.
+    Background
+}
+
+/// Number of `JobPriority` levels.
+pub(crate) const PRIORITY_COUNT: usize = 4;
+
+impl JobPriority {
+    /// Every priority, from most to least urgent.
+    pub const ALL: [JobPriority; PRIORITY_COUNT] = [JobPriority::Critical, JobPriority::High, JobPriority::Normal, JobPriority::Background];
+
+    /// Index of the priority's queue, where 0 is the most urgent.
+    pub(crate) fn index(self) -> usize {
+        return self as usize;
+    }
+}
+
+/// Picks which priority a job thread should take its next job from. Keeps count of how many jobs ran ahead of each
+/// waiting priority, and lets a priority go first once that reaches the starvation limit.
+pub(crate) struct StarvationGuard {
+    skipped: [usize; PRIORITY_COUNT],
+    limit: usize
+}
+
+impl StarvationGuard {
+    pub(crate) fn new(limit: usize) -> Self {
+        return StarvationGuard { skipped: [0; PRIORITY_COUNT], limit };
+    }
+
+    /// Given which priorities have waiting jobs, returns the index of the priority to take a job from, if any.
+    pub(crate) fn next_priority(&mut self, has_work: [bool; PRIORITY_COUNT]) -> Option<usize> {
+        // The least urgent starving priority has been skipped by the most, so it goes first.
+        let starving = (0..PRIORITY_COUNT).rev().find(|&index| has_work[index] && self.skipped[index] >= self.limit);
+        let chosen = starving.or_else(|| (0..PRIORITY_COUNT).find(|&index| has_work[index]))?;
+        for (index, skipped) in self.skipped.iter_mut().enumerate() {
+            if index == chosen || !has_work[index] {
+                *skipped = 0;
+            } else if index > chosen {
+                *skipped += 1;
+            }
+        }
+        return Some(chosen);
+    }
+}
@@ -1,48 +1,77 @@
-use super::{job_container::JobContainer, system::QUEUE_CAPACITY};
+use super::job_container::JobContainer;
 
 pub(crate) struct JobRingQueue {
-    pub(crate) buffer: Box<[JobContainer]>,
-    pub(crate) length: usize,
-    pub(crate) read_index: usize,
-    pub(crate) write_index: usize
+    buffer: Box<[JobContainer]>,
+    length: usize,
+    read_index: usize,
+    write_index: usize
 }
 
 impl JobRingQueue {
-    pub(crate) fn new() -> Self {
-        let mut v: Vec<JobContainer> = Vec::with_capacity(QUEUE_CAPACITY);
-        for _ in 0..QUEUE_CAPACITY {
-            v.push(JobContainer::default());
-        }
-        return JobRingQueue { 
-            buffer: v.into_boxed_slice(), 
-            length: 0, 
-            read_index: 0, 
-            write_index: 0 
+    pub(crate) fn new(capacity: usize) -> Self {
+        debug_assert_ne!(capacity, 0, "Cannot create a job ring queue with 0 capacity");
+        return JobRingQueue {
+            buffer: Self::make_buffer(capacity),
+            length: 0,
+            read_index: 0,
+            write_index: 0
         }
     }
 
+    pub(crate) fn len(&self) -> usize {
+        return self.length;
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        return self.buffer.len();
+    }
+
     pub(crate) fn is_full(&self) -> bool {
-        return self.length == QUEUE_CAPACITY;
+        return self.length == self.capacity();
     }
 
-    // pub(crate) fn is_empty(&self) -> bool {
-    //     return self.length == 0;
-    // }
+    pub(crate) fn is_empty(&self) -> bool {
+        return self.length == 0;
+    }
 
+    /// The queue must not be full. Check `is_full()` and `grow()` first.
     pub(crate) fn push(&mut self, mut job: JobContainer) {
-        assert!(!self.is_full(), "Job ring queue is full");
+        debug_assert!(!self.is_full(), "Job ring queue is full");
 
         std::mem::swap(&mut self.buffer[self.write_index], &mut job);
-        self.write_index = (self.write_index + 1) % QUEUE_CAPACITY;
+        self.write_index = (self.write_index + 1) % self.capacity();
         self.length += 1;
     }
 
-    // pub(crate) fn pop(&mut self) -> Job {
-    //     assert!(!self.is_empty(), "Job ring queue is empty");
+    pub(crate) fn pop(&mut self) -> Option<JobContainer> {
+        if self.is_empty() {
+            return None;
+        }
 
-    //     let out_job = std::mem::take(&mut self.buffer[self.read_index]);
-    //     self.read_index = (self.read_index + 1) % QUEUE_CAPACITY;
-    //     self.length -= 1;
-    //     return out_job;
-    // }
-}
\ No newline at end of file
+        let out_job = std::mem::take(&mut self.buffer[self.read_index]);
+        self.read_index = (self.read_index + 1) % self.capacity();
+        self.length -= 1;
+        return Some(out_job);
+    }
+
+    /// Doubles the capacity, keeping the queued jobs in order.
+    pub(crate) fn grow(&mut self) {
+        let mut new_buffer = Self::make_buffer(self.capacity() * 2);
+        let length = self.length;
+        for slot in new_buffer.iter_mut().take(length) {
+            *slot = self.pop().unwrap();
+        }
+        self.buffer = new_buffer;
+        self.length = length;
+        self.read_index = 0;
+        self.write_index = length;
+    }
+
+    fn make_buffer(capacity: usize) -> Box<[JobContainer]> {
+        let mut v: Vec<JobContainer> = Vec::with_capacity(capacity);
+        for _ in 0..capacity {
+            v.push(JobContainer::default());
+        }
+        return v.into_boxed_slice();
+    }
+}
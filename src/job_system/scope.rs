@@ -0,0 +1,228 @@
+use std::{marker::PhantomData, panic::{self, AssertUnwindSafe}, sync::{atomic::{AtomicUsize, Ordering}, Arc, Condvar, Mutex}, thread};
+use super::{system::JobSystem, job_container::JobContainer, priority::JobPriority};
+
+struct ScopeState {
+    // Spawned jobs that have not finished running yet.
+    pending: AtomicUsize,
+    // Spawned jobs that panicked, and whose handle has not been joined.
+    unjoined_panics: AtomicUsize,
+    // Notified once `pending` reaches 0, or when a job is spawned that no queue accepted, for the scope to run it.
+    changed: (Mutex<()>, Condvar)
+}
+
+// Result of a `ScopedJob`, along with the threads sleeping until it is set.
+struct ScopedResult<T> {
+    value: Option<thread::Result<T>>,
+    waiter_count: usize
+}
+
+// Type erased view of a `ScopedJob`, for the scope and the queued job.
+trait ScopedRun: Send + Sync {
+    fn try_run(&self) -> bool;
+    fn clear_result(&self);
+}
+
+// Shared by the queued job, the handle and the scope. Whichever claims `func` first runs it, so a job that has not
+// started yet can be run by the thread waiting on it.
+struct ScopedJob<'scope, T> {
+    func: Mutex<Option<Box<dyn FnOnce() -> T + Send + 'scope>>>,
+    result: Mutex<ScopedResult<T>>,
+    // Notified once the result is set, if anyone is waiting on it.
+    finished: Condvar,
+    state: Arc<ScopeState>
+}
+
+impl<'scope, T> ScopedRun for ScopedJob<'scope, T>
+where T: Send {
+    /// Runs the job unless it has already been claimed. Returns if it ran it.
+    fn try_run(&self) -> bool {
+        let func = match self.func.lock().unwrap().take() {
+            Some(func) => func,
+            None => return false
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(func));
+        if result.is_err() {
+            self.state.unjoined_panics.fetch_add(1, Ordering::AcqRel);
+        }
+        {
+            let mut job_result = self.result.lock().unwrap();
+            job_result.value = Some(result);
+            if job_result.waiter_count > 0 {
+                self.finished.notify_all();
+            }
+        }
+        // Last, since the scope may return as soon as nothing is pending. Only the shared state is touched afterwards.
+        if self.state.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+            let _lock = self.state.changed.0.lock().unwrap();
+            self.state.changed.1.notify_all();
+        }
+        return true;
+    }
+
+    fn clear_result(&self) {
+        let result = self.result.lock().unwrap().value.take();
+        drop(result);
+    }
+}
+
+/// Spawns jobs that may borrow from outside the scope. Made by `JobSystem::scope()`, which waits for
+/// every spawned job to complete before returning.
+pub struct JobScope<'scope, 'env: 'scope> {
+    job_system: &'scope JobSystem,
+    state: Arc<ScopeState>,
+    // Lifetime erased, so the scope can be borrowed for its whole lifetime. See `spawn_with_priority()`.
+    jobs: Mutex<Vec<Arc<dyn ScopedRun>>>,
+    // Invariant over both lifetimes, same as `std::thread::Scope`.
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>
+}
+
+/// Runs `func` with a new scope, then waits for every job spawned in it. See `JobSystem::scope()`.
+pub(crate) fn run_scope<'env, F, T>(job_system: &'env JobSystem, func: F) -> T
+where F: for<'s> FnOnce(&'s JobScope<'s, 'env>) -> T {
+    let scope = JobScope {
+        job_system,
+        state: Arc::new(ScopeState {
+            pending: AtomicUsize::new(0),
+            unjoined_panics: AtomicUsize::new(0),
+            changed: (Mutex::new(()), Condvar::new())
+        }),
+        jobs: Mutex::new(Vec::new()),
+        scope: PhantomData,
+        env: PhantomData
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| func(&scope)));
+    scope.complete_jobs();
+
+    match result {
+        Ok(value) => {
+            assert_eq!(scope.state.unjoined_panics.load(Ordering::Acquire), 0, "a scoped job panicked");
+            return value;
+        },
+        Err(payload) => panic::resume_unwind(payload)
+    }
+}
+
+impl<'scope, 'env> JobScope<'scope, 'env> {
+    /// Queues a job on the job system that may borrow anything that outlives the scope.
+    /// If every queue refuses the job, it runs when it is joined or when the scope ends instead.
+    /// Results that are never joined are dropped before the scope returns, so they may borrow from outside it too.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// // Counts how many of its results were dropped, through a borrow of a local.
+    /// struct Chunk<'a> { dropped: &'a AtomicUsize }
+    ///
+    /// impl Drop for Chunk<'_> {
+    ///     fn drop(&mut self) {
+    ///         self.dropped.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// }
+    ///
+    /// let job_system = JobSystem::new_init(2);
+    /// let dropped = AtomicUsize::new(0);
+    /// job_system.scope(|s| {
+    ///     for _ in 0..8 {
+    ///         s.spawn(|| Chunk { dropped: &dropped });
+    ///     }
+    ///     let joined = s.spawn(|| Chunk { dropped: &dropped }).join();
+    ///     drop(joined);
+    /// });
+    /// assert_eq!(dropped.load(Ordering::Relaxed), 9);
+    /// ```
+    pub fn spawn<F, T>(&'scope self, func: F) -> ScopedJobHandle<'scope, T>
+    where F: FnOnce() -> T + Send + 'scope, T: Send + 'scope {
+        return self.spawn_with_priority(JobPriority::Normal, func);
+    }
+
+    /// Queues a job with `priority`. See `spawn()` and `JobSystem::run_job_with_priority()`.
+    pub fn spawn_with_priority<F, T>(&'scope self, priority: JobPriority, func: F) -> ScopedJobHandle<'scope, T>
+    where F: FnOnce() -> T + Send + 'scope, T: Send + 'scope {
+        let job = Arc::new(ScopedJob {
+            func: Mutex::new(Some(Box::new(func) as Box<dyn FnOnce() -> T + Send + 'scope>)),
+            result: Mutex::new(ScopedResult { value: None, waiter_count: 0 }),
+            finished: Condvar::new(),
+            state: self.state.clone()
+        });
+        let erased_job: Arc<dyn ScopedRun + 'scope> = job.clone();
+        // SAFETY: The queued job may outlive the scope, since a job thread can hold its `Arc` after the scope returns.
+        // The only data borrowed for 'scope is `func`, with whatever it captured, and the result it returns.
+        // Before `run_scope()` returns, `complete_jobs()` makes sure both are gone from every spawned job:
+        // - `func` has been taken, since every job is either run by the scope or has finished running elsewhere,
+        //   which `pending` reaching 0 shows.
+        // - `clear_result()` has dropped the result, unless its handle already took it.
+        // After that, the only thing left to the queued job is `try_run()` finding no `func` to claim, and dropping
+        // its `Arc`, which drops the emptied `Mutex`es without touching borrowed data.
+        let erased_job: Arc<dyn ScopedRun + 'static> = unsafe { std::mem::transmute(erased_job) };
+        self.state.pending.fetch_add(1, Ordering::AcqRel);
+        self.jobs.lock().unwrap().push(erased_job.clone());
+
+        if self.job_system.try_push_job(JobContainer::new(move || { erased_job.try_run(); }), priority).is_err() {
+            // Wakes the scope if it is already waiting, since only it will run the job now.
+            let _lock = self.state.changed.0.lock().unwrap();
+            self.state.changed.1.notify_all();
+        }
+        return ScopedJobHandle { job, scope: PhantomData };
+    }
+
+    // Runs every job that has not started yet on the calling thread, then sleeps until the ones that have finish.
+    fn complete_jobs(&self) {
+        let mut checked_count = 0;
+        loop {
+            // Copied out of the lock, since running jobs may spawn more.
+            let unchecked: Vec<Arc<dyn ScopedRun>> = self.jobs.lock().unwrap()[checked_count..].to_vec();
+            checked_count += unchecked.len();
+            for job in unchecked.iter() {
+                job.try_run();
+            }
+            // Running jobs may spawn more, so wakes up for those as well, in case no queue accepted them.
+            let (lock, cvar) = &self.state.changed;
+            drop(cvar.wait_while(lock.lock().unwrap(), |_| {
+                self.state.pending.load(Ordering::Acquire) > 0 && self.jobs.lock().unwrap().len() == checked_count
+            }).unwrap());
+            if self.state.pending.load(Ordering::Acquire) == 0 {
+                break;
+            }
+        }
+        for job in self.jobs.lock().unwrap().drain(..) {
+            job.clear_result();
+        }
+    }
+}
+
+/// Handle to a job spawned in a `JobScope`.
+pub struct ScopedJobHandle<'scope, T> {
+    job: Arc<ScopedJob<'scope, T>>,
+    scope: PhantomData<&'scope ()>
+}
+
+impl<'scope, T> ScopedJobHandle<'scope, T>
+where T: Send {
+    /// Waits for the job to finish and takes its value. Runs it on the calling thread if it has not started yet.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the job's panic if it panicked.
+    pub fn join(self) -> T {
+        self.job.try_run();
+        let result = {
+            let mut job_result = self.job.result.lock().unwrap();
+            job_result.waiter_count += 1;
+            job_result = self.job.finished.wait_while(job_result, |job_result| job_result.value.is_none()).unwrap();
+            job_result.waiter_count -= 1;
+            job_result.value.take().unwrap()
+        };
+        match result {
+            Ok(value) => return value,
+            Err(payload) => {
+                self.job.state.unjoined_panics.fetch_sub(1, Ordering::AcqRel);
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+
+    /// Checks if the job has finished running.
+    pub fn is_finished(&self) -> bool {
+        return self.job.result.lock().unwrap().value.is_some();
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+
+/// Number of jobs each job thread's queue can hold before `JobSystemSettings::queue_full_policy` applies.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 8192;
+
+/// Number of more urgent jobs that may run ahead of a waiting job before it runs anyway.
+pub const DEFAULT_STARVATION_LIMIT: usize = 32;
+
+/// What happens when a job is queued onto a job thread whose queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+    /// Doubles the queue's capacity. Never fails, but bursts can use a lot of memory.
+    Grow,
+    /// Blocks the queueing thread until the job thread takes jobs off the queue, applying backpressure.
+    /// Jobs queueing more jobs never block, since that could deadlock the job threads, and grow the queue instead.
+    Block,
+    /// Refuses the job. `try_run_job()` returns a `QueueFullError`, and `run_job()` panics.
+    Reject
+}
+
+/// How a `JobSystem` distributes jobs over its job threads.
+/// ```
+/// # use gk_types_rs::job_system::system::JobSystem;
+/// # use gk_types_rs::job_system::settings::{JobSystemSettings, Scheduler};
+/// # use std::{thread, time::Duration};
+/// let settings = JobSystemSettings { scheduler: Scheduler::WorkStealing, ..Default::default() };
+/// let job_system = JobSystem::new_init_with_settings(2, settings);
+/// // The long job does not hold up the short ones, which the other thread takes.
+/// let long = job_system.run_job(|| thread::sleep(Duration::from_millis(50)));
+/// let short: Vec<_> = (0..100).map(|i| job_system.run_job(move || i * 2)).collect();
+/// for (i, future) in short.iter().enumerate() {
+///     assert_eq!(future.wait(), i * 2);
+/// }
+/// long.wait();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheduler {
+    /// Each job is assigned to a single job thread's queue when it is run, preferring idle threads with
+    /// the shortest queues. A long job delays every job queued behind it on the same thread.
+    LoadBalanced,
+    /// Jobs run from outside the job system go to a shared queue, and jobs run from within jobs go to the
+    /// running thread's own deque. Idle threads take from the shared queue, then steal from other threads' deques,
+    /// so a long job never holds up others. Queue capacity and the full queue policy apply to the shared queue,
+    /// which holds `queue_capacity` jobs per thread. A job thread's own deque always grows.
+    WorkStealing
+}
+
+/// What happens when a job panics. Either way, the panic message is printed by the panic hook first.
+/// Jobs spawned in a `JobScope`, and the chunks of `JobSystem::parallel_for()`, always resume their panics on the
+/// calling thread, since it waits on them.
+/// ```
+/// # use gk_types_rs::job_system::system::JobSystem;
+/// # use gk_types_rs::job_system::settings::{JobSystemSettings, PanicPolicy};
+/// let settings = JobSystemSettings { panic_policy: PanicPolicy::Propagate, ..Default::default() };
+/// let job_system = JobSystem::new_init_with_settings(1, settings);
+/// let failed = job_system.run_job(|| -> u32 { panic!("navmesh is corrupt") });
+/// assert!(failed.try_wait().is_err());
+/// // The job thread survived the panic.
+/// assert_eq!(job_system.run_job(|| 7).wait(), 7);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Catches the panic, keeping the job thread alive, and resumes it on whoever waits on the job's `JobFuture`.
+    Propagate,
+    /// Aborts the process, for games where a half finished job leaves state that cannot be recovered.
+    Abort
+}
+
+/// Configuration of a `JobSystem`, given on creation.
+/// ```
+/// # use gk_types_rs::job_system::system::JobSystem;
+/// # use gk_types_rs::job_system::settings::{JobSystemSettings, QueueFullPolicy};
+/// let settings = JobSystemSettings { queue_capacity: 16, queue_full_policy: QueueFullPolicy::Block, ..Default::default() };
+/// let job_system = JobSystem::new_init_with_settings(2, settings);
+/// let futures: Vec<_> = (0..1000).map(|i| job_system.run_job(move || i)).collect();
+/// for (i, future) in futures.iter().enumerate() {
+///     assert_eq!(future.wait(), i);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobSystemSettings {
+    /// Initial number of jobs each job thread's queue can hold, per `JobPriority`. Must not be 0.
+    pub queue_capacity: usize,
+    /// What happens when a job thread's queue is full.
+    pub queue_full_policy: QueueFullPolicy,
+    /// How jobs are distributed over the job threads.
+    pub scheduler: Scheduler,
+    /// Number of more urgent jobs a job thread may run while a less urgent job is waiting, before running that job anyway.
+    /// Keeps a steady stream of `JobPriority::Critical` jobs from starving `JobPriority::Background` jobs. Must not be 0.
+    pub starvation_limit: usize,
+    /// What happens when a job panics.
+    pub panic_policy: PanicPolicy
+}
+
+impl Default for JobSystemSettings {
+    /// Load balanced queues of `DEFAULT_QUEUE_CAPACITY` jobs that grow when full, with a `DEFAULT_STARVATION_LIMIT`,
+    /// propagating job panics to their futures.
+    fn default() -> Self {
+        return JobSystemSettings {
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            queue_full_policy: QueueFullPolicy::Grow,
+            scheduler: Scheduler::LoadBalanced,
+            starvation_limit: DEFAULT_STARVATION_LIMIT,
+            panic_policy: PanicPolicy::Propagate
+        };
+    }
+}
+
+impl JobSystemSettings {
+    /// Checked by everything made from settings, before any job thread starts.
+    pub(crate) fn assert_valid(&self) {
+        assert_ne!(self.queue_capacity, 0, "JobSystemSettings::queue_capacity must not be 0");
+        // A limit of 0 would count every waiting job as starving, running the least urgent jobs first.
+        assert_ne!(self.starvation_limit, 0, "JobSystemSettings::starvation_limit must not be 0");
+    }
+}
+
+/// Returned when a job cannot be queued because the queue is full, and the policy is `QueueFullPolicy::Reject`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFullError {
+    /// Capacity of the queue that was full.
+    pub capacity: usize
+}
+
+impl fmt::Display for QueueFullError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "job queue is full ({} jobs)", self.capacity);
+    }
+}
+
+impl std::error::Error for QueueFullError {}
@@ -0,0 +1,64 @@
+//! Synchronization primitives used by the job queues, job threads and futures. Built with `--cfg loom`, they are
+//! swapped for loom's, so that every interleaving of them can be model checked.
+
+#[cfg(loom)]
+pub(crate) use loom::{cell::UnsafeCell, thread, sync::{Mutex, atomic::{fence, AtomicBool, AtomicIsize, AtomicPtr, AtomicUsize, Ordering}}};
+
+#[cfg(not(loom))]
+pub(crate) use std::{thread, sync::{Condvar, Mutex, atomic::{fence, AtomicBool, AtomicIsize, AtomicPtr, AtomicUsize, Ordering}}};
+
+/// loom's `Condvar`, with the predicate based waits of `std::sync::Condvar` built on top of it.
+#[cfg(loom)]
+pub(crate) struct Condvar(loom::sync::Condvar);
+
+#[cfg(loom)]
+impl Condvar {
+    pub(crate) fn new() -> Self {
+        return Condvar(loom::sync::Condvar::new());
+    }
+
+    pub(crate) fn notify_one(&self) {
+        self.0.notify_one();
+    }
+
+    pub(crate) fn notify_all(&self) {
+        self.0.notify_all();
+    }
+
+    pub(crate) fn wait<'a, T>(&self, guard: loom::sync::MutexGuard<'a, T>) -> std::sync::LockResult<loom::sync::MutexGuard<'a, T>> {
+        return self.0.wait(guard);
+    }
+
+    pub(crate) fn wait_while<'a, T, F>(&self, mut guard: loom::sync::MutexGuard<'a, T>, mut condition: F)
+        -> std::sync::LockResult<loom::sync::MutexGuard<'a, T>>
+    where F: FnMut(&mut T) -> bool {
+        while condition(&mut *guard) {
+            guard = self.0.wait(guard)?;
+        }
+        return Ok(guard);
+    }
+
+    /// loom does not model time, so this never times out, and only returns once `condition` is false.
+    pub(crate) fn wait_timeout_while<'a, T, F>(&self, guard: loom::sync::MutexGuard<'a, T>, _timeout: std::time::Duration, condition: F)
+        -> std::sync::LockResult<(loom::sync::MutexGuard<'a, T>, ())>
+    where F: FnMut(&mut T) -> bool {
+        // loom never poisons its mutexes.
+        let guard = self.wait_while(guard, condition).unwrap_or_else(std::sync::PoisonError::into_inner);
+        return Ok((guard, ()));
+    }
+}
+
+/// `std::cell::UnsafeCell` with the closure based access of loom's, so that loom can track every access.
+#[cfg(not(loom))]
+pub(crate) struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    pub(crate) fn new(data: T) -> Self {
+        return UnsafeCell(std::cell::UnsafeCell::new(data));
+    }
+
+    pub(crate) fn with_mut<R>(&self, func: impl FnOnce(*mut T) -> R) -> R {
+        return func(self.0.get());
+    }
+}
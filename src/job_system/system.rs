@@ -1,17 +1,109 @@
-use std::{sync::atomic::{AtomicUsize, Ordering}, thread, mem::MaybeUninit, cell::UnsafeCell};
-use super::{thread::JobThread, future::JobFuture};
-
-pub(crate) const QUEUE_CAPACITY: usize = 8192;
+use std::{future::Future, ops::Range, sync::{atomic::{AtomicUsize, Ordering}, Arc, Condvar, Mutex}, thread};
+use super::{thread::JobThread, future::{JobFuture, JobDependency, JobError, WithinJobFuture, catch_job_panic}, job_container::JobContainer, work_stealing::WorkStealingPool,
+    graph::{DependentJob, JobGraph, ReleaseJob}, async_task::{AsyncTask, FinishTask}, settings::{JobSystemSettings, QueueFullError, Scheduler}, priority::JobPriority,
+    parallel::{self, ParIterMut}, scope::{self, JobScope}, cancellation::CancellationToken};
 
 struct Inner {
     threads: Box<[Box<JobThread>]>,
+    // Replaces `threads` when using `Scheduler::WorkStealing`.
+    work_stealing: Option<WorkStealingPool>,
     // MUST not mutate
     thread_count: usize,
-    current_optimal_thread: AtomicUsize
+    current_optimal_thread: AtomicUsize,
+    settings: JobSystemSettings,
+    // Jobs from `run_job_after()` and `run_graph()` that are still waiting on prerequisites,
+    // and futures from `spawn_async()` that have not completed.
+    pending_dependents: AtomicUsize,
+    // Notified once `pending_dependents` reaches 0, for `JobSystem::wait()`.
+    dependents_released: (Mutex<()>, Condvar)
 }
 
 impl Inner {
-    fn get_optimal_thread_for_execution(&mut self) -> usize {
+    fn new(thread_count: usize, settings: JobSystemSettings) -> Self {
+        let (threads, work_stealing) = Self::make_threads(thread_count, settings);
+        return Inner {
+            threads,
+            work_stealing,
+            thread_count,
+            current_optimal_thread: AtomicUsize::new(0),
+            settings,
+            pending_dependents: AtomicUsize::new(0),
+            dependents_released: (Mutex::new(()), Condvar::new())
+        }
+    }
+
+    fn make_threads(thread_count: usize, settings: JobSystemSettings) -> (Box<[Box<JobThread>]>, Option<WorkStealingPool>) {
+        if settings.scheduler == Scheduler::WorkStealing {
+            return (Box::new([]), Some(WorkStealingPool::new(thread_count, settings)));
+        }
+        let mut v: Vec<Box<JobThread>> = Vec::with_capacity(thread_count);
+        for _ in 0..thread_count {
+            v.push(JobThread::new_with_settings(settings));
+        }
+        return (v.into_boxed_slice(), None);
+    }
+
+    fn try_push_job(&self, mut job: JobContainer, priority: JobPriority) -> Result<(), (JobContainer, QueueFullError)> {
+        if let Some(work_stealing) = &self.work_stealing {
+            return work_stealing.try_push_job(job, priority);
+        }
+        let optimal_thread_index = self.get_optimal_thread_for_execution();
+        let mut queue_full = None;
+        // A full queue gives the job back, so the other threads can be tried.
+        for i in 0..self.thread_count {
+            let job_thread = &self.threads[(optimal_thread_index + i) % self.thread_count];
+            match (*job_thread).try_push_job(job, priority) {
+                Ok(()) => {
+                    (*job_thread).execute();
+                    return Ok(());
+                },
+                Err((refused_job, error)) => {
+                    job = refused_job;
+                    queue_full = Some(error);
+                }
+            }
+        }
+        return Err((job, queue_full.unwrap()));
+    }
+
+    /// Queues a job from whichever thread released it, running it right away if every queue refuses it.
+    fn queue_released_job(&self, job: JobContainer) {
+        if let Err((mut job, _)) = self.try_push_job(job, JobPriority::Normal) {
+            // Nothing can report the error at this point, so run it rather than lose it.
+            job.invoke();
+        }
+    }
+
+    /// Queues a dependent job whose prerequisites have all finished.
+    fn release_job(&self, job: JobContainer) {
+        self.queue_released_job(job);
+        self.finish_dependent();
+    }
+
+    /// Counts off a released dependent job or a completed async task, waking `JobSystem::wait()` once none are left.
+    fn finish_dependent(&self) {
+        if self.pending_dependents.fetch_sub(1, Ordering::AcqRel) == 1 {
+            let _lock = self.dependents_released.0.lock().unwrap();
+            self.dependents_released.1.notify_all();
+        }
+    }
+
+    fn release_job_fn(&self) -> ReleaseJob {
+        let handle = InnerHandle(self as *const Inner);
+        return Arc::new(move |job| unsafe { handle.get() }.release_job(job));
+    }
+
+    fn queue_released_job_fn(&self) -> ReleaseJob {
+        let handle = InnerHandle(self as *const Inner);
+        return Arc::new(move |job| unsafe { handle.get() }.queue_released_job(job));
+    }
+
+    fn finish_dependent_fn(&self) -> FinishTask {
+        let handle = InnerHandle(self as *const Inner);
+        return Box::new(move || unsafe { handle.get() }.finish_dependent());
+    }
+
+    fn get_optimal_thread_for_execution(&self) -> usize {
         let previous_optimal = self.current_optimal_thread.load(Ordering::Acquire);
         let mut minimum_queue_load = usize::MAX;
         let mut is_optimal_executing = true;
@@ -26,13 +118,11 @@ impl Inner {
                 return check_index;
             }
 
-            if is_not_executing {
-                if minimum_queue_load > queue_load {
-                    current_optimal = check_index;
-                    minimum_queue_load = queue_load;
-                    is_optimal_executing = false;
-                    continue;
-                }
+            if is_not_executing && minimum_queue_load > queue_load {
+                current_optimal = check_index;
+                minimum_queue_load = queue_load;
+                is_optimal_executing = false;
+                continue;
             }
 
             if minimum_queue_load > queue_load && is_optimal_executing {
@@ -45,18 +135,30 @@ impl Inner {
     }
 }
 
+// Lets released dependent jobs reach the boxed Inner from whichever thread releases them.
+struct InnerHandle(*const Inner);
+
+// Inner is Sync, and the boxed Inner outlives every dependent job and async task, since the JobSystem waits for them
+// before dropping it or replacing its threads.
+unsafe impl Send for InnerHandle {}
+unsafe impl Sync for InnerHandle {}
+
+impl InnerHandle {
+    /// The JobSystem owning the Inner must still be waiting on a dependent job or async task.
+    unsafe fn get(&self) -> &Inner {
+        return &*self.0;
+    }
+}
+
 /// Container to hold and dispatch jobs across a varying amount of threads. 
 /// Can optionally be created in an uninitialized state, which can be initialized later with a specific number of threads.
 /// Cannot be used in the uninitialized state. The thread count can be changed at runtime.
 /// All operations on the job system are thread safe.
 pub struct JobSystem {
-    inner: UnsafeCell<MaybeUninit<Inner>>,
-    is_initialized: bool
+    // Boxed so that released dependent jobs can reach it, even if the JobSystem is moved. None until initialized.
+    inner: Option<Box<Inner>>
 }
 
-unsafe impl Send for JobSystem {}
-unsafe impl Sync for JobSystem {}
-
 impl JobSystem {
     /// Creates an uninitialized, thread safe JobSystem object. It does no allocation until `init()` is called.
     /// 
@@ -78,10 +180,7 @@ impl JobSystem {
     /// job_system.run_job(|| 1);
     /// ```
     pub const fn new_uninit() -> JobSystem {
-        return JobSystem { 
-            inner: UnsafeCell::new(MaybeUninit::uninit()),
-            is_initialized: false
-        }
+        return JobSystem { inner: None }
     }
 
     /// Create a new job system object given a specific number of threads.
@@ -100,19 +199,34 @@ impl JobSystem {
     /// job_system.run_job(|| 1);
     /// ```
     pub fn new_init(thread_count: usize) -> JobSystem {
-        debug_assert_ne!(thread_count, 0, "Cannot create a job system using 0 threads");
-        let mut v: Vec<Box<JobThread>> = Vec::with_capacity(QUEUE_CAPACITY);
-        for _ in 0..thread_count {
-            v.push(JobThread::new());
-        }
-        return JobSystem { 
-            inner: UnsafeCell::new(MaybeUninit::new(Inner {
-                threads: v.into_boxed_slice(),
-                thread_count,
-                current_optimal_thread: AtomicUsize::new(0),
-            })), 
-            is_initialized: true
-        }
+        return Self::new_init_with_settings(thread_count, JobSystemSettings::default());
+    }
+
+    /// Create a new job system object given a specific number of threads, with each job thread's queue
+    /// behaving according to `settings`. See `new_init()`.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `thread_count`, `settings.queue_capacity` or `settings.starvation_limit` is 0.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use gk_types_rs::job_system::settings::JobSystemSettings;
+    /// let job_system = JobSystem::new_init_with_settings(1, JobSystemSettings { queue_capacity: 4, ..Default::default() });
+    /// assert_eq!(job_system.run_job(|| 1).wait(), 1);
+    /// ```
+    /// Queues must be able to hold at least one job, in release builds too.
+    /// ``` should_panic
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use gk_types_rs::job_system::settings::JobSystemSettings;
+    /// let job_system = JobSystem::new_init_with_settings(1, JobSystemSettings { queue_capacity: 0, ..Default::default() });
+    /// ```
+    pub fn new_init_with_settings(thread_count: usize, settings: JobSystemSettings) -> JobSystem {
+        assert_ne!(thread_count, 0, "Cannot create a job system using 0 threads");
+        settings.assert_valid();
+        return JobSystem { inner: Some(Box::new(Inner::new(thread_count, settings))) }
     }
 
     /// Initializes an uninitalized JobSystem with a given thread count.
@@ -139,18 +253,21 @@ impl JobSystem {
     /// job_system.init(max_available_job_threads());
     /// ```
     pub fn init(&mut self, thread_count: usize) {
-        debug_assert_ne!(thread_count, 0, "Cannot create a job system using 0 threads");
-        assert!(!self.is_initialized, "JobSystem is already initialized");
-        let mut v: Vec<Box<JobThread>> = Vec::with_capacity(QUEUE_CAPACITY);
-        for _ in 0..thread_count {
-            v.push(JobThread::new());
-        }
-        self.inner = UnsafeCell::new(MaybeUninit::new(Inner {
-            threads: v.into_boxed_slice(),
-            thread_count,
-            current_optimal_thread: AtomicUsize::new(0),
-        }));
-        self.is_initialized = true;
+        self.init_with_settings(thread_count, JobSystemSettings::default());
+    }
+
+    /// Initializes an uninitalized JobSystem with a given thread count, with each job thread's queue
+    /// behaving according to `settings`. See `init()`.
+    /// 
+    /// # Panics
+    /// 
+    /// Cannot call `init_with_settings()` if the JobSystem is already initialized.
+    /// Also panics if `thread_count`, `settings.queue_capacity` or `settings.starvation_limit` is 0.
+    pub fn init_with_settings(&mut self, thread_count: usize, settings: JobSystemSettings) {
+        assert_ne!(thread_count, 0, "Cannot create a job system using 0 threads");
+        settings.assert_valid();
+        assert!(self.inner.is_none(), "JobSystem is already initialized");
+        self.inner = Some(Box::new(Inner::new(thread_count, settings)));
         thread::yield_now();
     }
 
@@ -178,25 +295,30 @@ impl JobSystem {
     /// job_system.change_thread_count(4);
     /// ```
     pub fn change_thread_count(&mut self, new_thread_count: usize) {
-        debug_assert_ne!(new_thread_count, 0, "Cannot change JobSystem thread count using 0 threads");
-        assert!(self.is_initialized, "JobSystem is not initialized");
+        assert_ne!(new_thread_count, 0, "Cannot change JobSystem thread count using 0 threads");
+        assert!(self.inner.is_some(), "JobSystem is not initialized");
 
-        let inner = unsafe {self.inner.get_mut().assume_init_mut() };
-        for job_thread in inner.threads.iter() {
-            job_thread.wait();
-        }
+        self.wait();
+        let inner = self.inner.as_mut().unwrap();
 
-        let mut v: Vec<Box<JobThread>> = Vec::with_capacity(QUEUE_CAPACITY);
-        for _ in 0..new_thread_count {
-            v.push(JobThread::new());
-        }
-        inner.threads = v.into_boxed_slice();
+        // The old work stealing pool is dropped before the new one starts.
+        inner.work_stealing = None;
+        (inner.threads, inner.work_stealing) = Inner::make_threads(new_thread_count, inner.settings);
         inner.thread_count = new_thread_count;
         inner.current_optimal_thread.store(0, Ordering::Release);
         thread::yield_now();
     }
 
-    /// Queue and execute a job on one of the job threads. Automatic load balancing is done.
+    /// Queue and execute a job on one of the job threads. Jobs are distributed according to the `Scheduler` in the settings.
+    /// The job and its result move to and from a job thread, so both must be `Send`.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if every job thread's queue is full and the `QueueFullPolicy` is `Reject`.
+    /// Use `try_run_job()` to handle that instead.
+    /// 
+    /// # Examples
+    /// 
     /// ```
     /// # use gk_types_rs::job_system::{system::JobSystem, future::JobFuture};
     /// let job_system = JobSystem::new_init(2);
@@ -205,24 +327,459 @@ impl JobSystem {
     /// assert_eq!(future1.wait(), 123);
     /// assert_eq!(future2.wait(), 456);
     /// ```
+    /// Jobs cannot capture anything that is unsafe to use from another thread, such as an `Rc`.
+    /// ``` compile_fail
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use std::rc::Rc;
+    /// let job_system = JobSystem::new_init(2);
+    /// let count = Rc::new(1);
+    /// let job_count = count.clone();
+    /// job_system.run_job(move || *job_count + 1);
+    /// ```
     pub fn run_job<T, F>(&self, func: F) -> JobFuture<T>
-    where T: 'static, F: FnMut() -> T + 'static {
-        debug_assert!(self.is_initialized, "JobSystem is not initialized. Please call init()");
-        let job_thread = {
-            let inner = unsafe { (&mut *self.inner.get()).assume_init_mut() };
-            let optimal_thread_index = inner.get_optimal_thread_for_execution();
-            &mut inner.threads[optimal_thread_index]
-        };
-        let future = (*job_thread).queue_job(func);
-        (*job_thread).execute();
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
+        return self.try_run_job(func).expect("Every job thread's queue is full");
+    }
+
+    /// Queue and execute a job on one of the job threads, returning an error instead of panicking
+    /// if every job thread's queue is full and the `QueueFullPolicy` is `Reject`. Jobs are distributed according to the `Scheduler` in the settings.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use gk_types_rs::job_system::settings::{JobSystemSettings, QueueFullPolicy};
+    /// let settings = JobSystemSettings { queue_capacity: 4, queue_full_policy: QueueFullPolicy::Reject, ..Default::default() };
+    /// let job_system = JobSystem::new_init_with_settings(1, settings);
+    /// let mut futures = Vec::new();
+    /// for i in 0..1000 {
+    ///     match job_system.try_run_job(move || i) {
+    ///         Ok(future) => futures.push(future),
+    ///         // Backs off until the job thread makes room.
+    ///         Err(_) => job_system.wait()
+    ///     }
+    /// }
+    /// for future in futures {
+    ///     future.wait();
+    /// }
+    /// ```
+    pub fn try_run_job<T, F>(&self, func: F) -> Result<JobFuture<T>, QueueFullError>
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
+        return self.try_run_job_with_priority(JobPriority::Normal, func);
+    }
+
+    /// Queue and execute a job with `priority` on one of the job threads. Job threads take more urgent jobs first,
+    /// but run a waiting job anyway once `JobSystemSettings::starvation_limit` more urgent jobs have gone ahead of it.
+    /// Jobs run with `run_job()` are `JobPriority::Normal`.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if every job thread's queue for `priority` is full and the `QueueFullPolicy` is `Reject`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::job_system::{system::JobSystem, priority::JobPriority};
+    /// let job_system = JobSystem::new_init(2);
+    /// let decoded = job_system.run_job_with_priority(JobPriority::Background, || vec![0u8; 1024]);
+    /// // Does not wait behind the background job on its thread.
+    /// let culled = job_system.run_job_with_priority(JobPriority::Critical, || 42);
+    /// assert_eq!(culled.wait(), 42);
+    /// assert_eq!(decoded.wait().len(), 1024);
+    /// ```
+    pub fn run_job_with_priority<T, F>(&self, priority: JobPriority, func: F) -> JobFuture<T>
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
+        return self.try_run_job_with_priority(priority, func).expect("Every job thread's queue is full");
+    }
+
+    /// Queue and execute a job with `priority` on one of the job threads, returning an error instead of panicking
+    /// if every job thread's queue for `priority` is full and the `QueueFullPolicy` is `Reject`. See `run_job_with_priority()`.
+    pub fn try_run_job_with_priority<T, F>(&self, priority: JobPriority, func: F) -> Result<JobFuture<T>, QueueFullError>
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
+        let inner = self.inner();
+        let (future, job) = JobThread::make_job(inner.settings.panic_policy, func);
+        return inner.try_push_job(job, priority).map(|_| future).map_err(|(_, queue_full)| queue_full);
+    }
+
+    /// Queue a job that can be cancelled with `token`. If `token` is cancelled before the job starts, the job is dropped
+    /// without running, and the returned future resolves to `JobError::Cancelled`, right away on the cancelling thread.
+    /// The job is given the token, so it can check it and return early. If `token` is cancelled while the job runs,
+    /// its result is discarded, and the future also resolves to `JobError::Cancelled`.
+    /// 
+    /// Cancelling does not take the job off its queue, so a cancelled job leaves a small empty entry behind, which keeps
+    /// its queue slot, counting towards `JobSystemSettings::queue_capacity`, until a job thread reaches and skips it.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if every job thread's queue is full and the `QueueFullPolicy` is `Reject`. Use `try_run_cancellable_job()`
+    /// to handle that instead.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::job_system::{system::JobSystem, cancellation::CancellationToken, future::JobError};
+    /// # use std::{thread, time::Duration};
+    /// let job_system = JobSystem::new_init(2);
+    /// let token = CancellationToken::new();
+    /// let streaming = job_system.run_cancellable_job(&token, |token| {
+    ///     let mut loaded_chunks = 0;
+    ///     while !token.is_cancelled() {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///         loaded_chunks += 1;
+    ///     }
+    ///     loaded_chunks
+    /// });
+    /// // The level was unloaded, so stop streaming it.
+    /// token.cancel();
+    /// assert!(matches!(streaming.try_wait(), Err(JobError::Cancelled)));
+    /// ```
+    pub fn run_cancellable_job<T, F>(&self, token: &CancellationToken, func: F) -> JobFuture<T>
+    where T: Send + 'static, F: FnOnce(&CancellationToken) -> T + Send + 'static {
+        return self.run_cancellable_job_with_priority(JobPriority::Normal, token, func);
+    }
+
+    /// Queue a job that can be cancelled with `token`, with `priority`. See `run_cancellable_job()` and
+    /// `run_job_with_priority()`.
+    pub fn run_cancellable_job_with_priority<T, F>(&self, priority: JobPriority, token: &CancellationToken, func: F) -> JobFuture<T>
+    where T: Send + 'static, F: FnOnce(&CancellationToken) -> T + Send + 'static {
+        return self.try_run_cancellable_job_with_priority(priority, token, func).expect("Every job thread's queue is full");
+    }
+
+    /// Queue a job that can be cancelled with `token`, returning an error instead of panicking if every job thread's
+    /// queue is full and the `QueueFullPolicy` is `Reject`. See `run_cancellable_job()`.
+    /// ```
+    /// # use gk_types_rs::job_system::{system::JobSystem, cancellation::CancellationToken, future::JobError};
+    /// # use gk_types_rs::job_system::settings::{JobSystemSettings, QueueFullPolicy};
+    /// # use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, thread, time::Duration};
+    /// let settings = JobSystemSettings { queue_capacity: 2, queue_full_policy: QueueFullPolicy::Reject, ..Default::default() };
+    /// let job_system = JobSystem::new_init_with_settings(1, settings);
+    /// let (is_started, is_blocked) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(true)));
+    /// let (started, blocked) = (is_started.clone(), is_blocked.clone());
+    /// // Keeps the only job thread busy, once it has taken the job off its queue.
+    /// let blocker = job_system.run_job(move || {
+    ///     started.store(true, Ordering::Release);
+    ///     while blocked.load(Ordering::Acquire) {
+    ///         thread::sleep(Duration::from_millis(1));
+    ///     }
+    /// });
+    /// while !is_started.load(Ordering::Acquire) {
+    ///     thread::sleep(Duration::from_millis(1));
+    /// }
+    ///
+    /// let token = CancellationToken::new();
+    /// let queued: Vec<_> = (0..2).map(|i| job_system.try_run_cancellable_job(&token, move |_| i).unwrap()).collect();
+    /// token.cancel();
+    /// // Resolved right away, while the job thread is still busy.
+    /// for future in queued {
+    ///     assert!(matches!(future.try_wait(), Err(JobError::Cancelled)));
+    /// }
+    /// // The cancelled jobs' empty entries still hold their slots, so the queue stays full until the job thread reaches them.
+    /// assert!(job_system.try_run_cancellable_job(&CancellationToken::new(), |_| 2).is_err());
+    ///
+    /// is_blocked.store(false, Ordering::Release);
+    /// blocker.wait();
+    /// job_system.wait();
+    /// ```
+    pub fn try_run_cancellable_job<T, F>(&self, token: &CancellationToken, func: F) -> Result<JobFuture<T>, QueueFullError>
+    where T: Send + 'static, F: FnOnce(&CancellationToken) -> T + Send + 'static {
+        return self.try_run_cancellable_job_with_priority(JobPriority::Normal, token, func);
+    }
+
+    /// Queue a job that can be cancelled with `token`, with `priority`, returning an error instead of panicking
+    /// if every job thread's queue for `priority` is full and the `QueueFullPolicy` is `Reject`.
+    /// See `try_run_cancellable_job()` and `run_job_with_priority()`.
+    pub fn try_run_cancellable_job_with_priority<T, F>(&self, priority: JobPriority, token: &CancellationToken, func: F)
+        -> Result<JobFuture<T>, QueueFullError>
+    where T: Send + 'static, F: FnOnce(&CancellationToken) -> T + Send + 'static {
+        let inner = self.inner();
+        let (future, job) = JobThread::make_cancellable_job(inner.settings.panic_policy, token.clone(), func);
+        return inner.try_push_job(job, priority).map(|_| future).map_err(|(_, queue_full)| queue_full);
+    }
+
+    /// Queue a job that only starts once every job in `prerequisites` has finished. No thread waits on the prerequisites.
+    /// The job is queued by whichever job completes its last prerequisite, or right away if they have all finished.
+    /// If every queue refuses it by then, it runs on the thread that released it instead.
+    /// 
+    /// Prerequisites must eventually complete, since `wait()` and dropping the JobSystem wait for every dependent job.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use std::sync::{Arc, Mutex};
+    /// let job_system = JobSystem::new_init(2);
+    /// let order = Arc::new(Mutex::new(Vec::new()));
+    /// let (order_a, order_b, order_c) = (order.clone(), order.clone(), order.clone());
+    /// let future_a = job_system.run_job(move || order_a.lock().unwrap().push('a'));
+    /// let future_b = job_system.run_job(move || { order_b.lock().unwrap().push('b'); 2 });
+    /// let future_c = job_system.run_job_after(&[&future_a, &future_b], move || order_c.lock().unwrap().push('c'));
+    /// future_c.wait();
+    /// assert_eq!(future_b.wait(), 2);
+    /// assert_eq!(order.lock().unwrap().last(), Some(&'c'));
+    /// ```
+    pub fn run_job_after<T, F>(&self, prerequisites: &[&dyn JobDependency], func: F) -> JobFuture<T>
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
+        let (future, job) = JobThread::make_job(self.inner().settings.panic_policy, func);
+        self.queue_job_after(prerequisites, job);
         return future;
     }
 
-    /// Wait for all of the job threads to finish execution.
+    /// Queue a job after `prerequisites`, like `run_job_after()`, whose future is set to the result `func` returns.
+    /// Used to hand a prerequisite's panic on, rather than running anything with it.
+    pub(crate) fn run_result_job_after<T, F>(&self, prerequisites: &[&dyn JobDependency], func: F) -> JobFuture<T>
+    where T: Send + 'static, F: FnOnce() -> Result<T, JobError> + Send + 'static {
+        let (future, job) = self.make_result_job(func);
+        self.queue_job_after(prerequisites, job);
+        return future;
+    }
+
+    /// Queue a job once the first of `prerequisites` has completed, whose future is set to the result `func` returns
+    /// given the index of that prerequisite. The other prerequisites are not waited for.
+    pub(crate) fn run_result_job_after_any<T, F>(&self, prerequisites: &[&dyn JobDependency], func: F) -> JobFuture<T>
+    where T: Send + 'static, F: FnOnce(usize) -> Result<T, JobError> + Send + 'static {
+        let first = Arc::new(AtomicUsize::new(usize::MAX));
+        let first_completed = first.clone();
+        let (future, job) = self.make_result_job(move || func(first_completed.load(Ordering::Acquire)));
+        let inner = self.inner();
+        inner.pending_dependents.fetch_add(1, Ordering::AcqRel);
+        // Released by the first prerequisite to complete, and by the extra one once every continuation is registered.
+        let dependent = DependentJob::new(job, 2, inner.release_job_fn());
+        for (index, prerequisite) in prerequisites.iter().enumerate() {
+            let (dependent, first) = (dependent.clone(), first.clone());
+            prerequisite.on_complete(Box::new(move || {
+                if first.compare_exchange(usize::MAX, index, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                    dependent.complete_prerequisite();
+                }
+            }));
+        }
+        dependent.complete_prerequisite();
+        return future;
+    }
+
+    fn make_result_job<T, F>(&self, func: F) -> (JobFuture<T>, JobContainer)
+    where T: Send + 'static, F: FnOnce() -> Result<T, JobError> + Send + 'static {
+        let panic_policy = self.inner().settings.panic_policy;
+        let (future, in_job_future) = WithinJobFuture::new();
+        let mut func = Some(func);
+        let job = JobContainer::new(move || {
+            let func = func.take().expect("Dependent job ran twice");
+            in_job_future.set(catch_job_panic(panic_policy, func).and_then(|result| result));
+        });
+        return (future, job);
+    }
+
+    fn queue_job_after(&self, prerequisites: &[&dyn JobDependency], job: JobContainer) {
+        let inner = self.inner();
+        inner.pending_dependents.fetch_add(1, Ordering::AcqRel);
+        // One extra prerequisite, so the job cannot be released before every continuation is registered.
+        let dependent = DependentJob::new(job, prerequisites.len() + 1, inner.release_job_fn());
+        for prerequisite in prerequisites {
+            let dependent = dependent.clone();
+            prerequisite.on_complete(Box::new(move || dependent.complete_prerequisite()));
+        }
+        dependent.complete_prerequisite();
+    }
+
+    /// Runs every job in `graph`, queueing each one once all of its prerequisites have finished.
+    /// The returned future completes once every job in the graph has. If a job panics, the jobs that have not started
+    /// yet are skipped, and the future holds the panic.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if a previous run of `graph` has not completed yet.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::job_system::{system::JobSystem, graph::JobGraphBuilder};
+    /// # use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    /// let job_system = JobSystem::new_init(2);
+    /// let (physics, animation) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    /// let mut builder = JobGraphBuilder::new();
+    /// let physics_job = builder.add_job({ let physics = physics.clone(); move || { physics.fetch_add(1, Ordering::Relaxed); } });
+    /// let animation_job = builder.add_job({ let animation = animation.clone(); move || { animation.fetch_add(1, Ordering::Relaxed); } });
+    /// let (physics_check, animation_check) = (physics.clone(), animation.clone());
+    /// builder.add_job_after(&[physics_job, animation_job], move || {
+    ///     assert_eq!(physics_check.load(Ordering::Relaxed), animation_check.load(Ordering::Relaxed));
+    /// });
+    /// let graph = builder.build();
+    /// // Compiled once, run every frame.
+    /// for _frame in 0..10 {
+    ///     job_system.run_graph(&graph).wait();
+    /// }
+    /// assert_eq!(physics.load(Ordering::Relaxed), 10);
+    /// ```
+    /// A panicking job skips its dependents, and the graph can run again afterwards.
+    /// ```
+    /// # use gk_types_rs::job_system::{system::JobSystem, graph::JobGraphBuilder};
+    /// let job_system = JobSystem::new_init(2);
+    /// let mut builder = JobGraphBuilder::new();
+    /// let load = builder.add_job(|| panic!("missing level file"));
+    /// builder.add_job_after(&[load], || unreachable!());
+    /// let graph = builder.build();
+    /// assert!(job_system.run_graph(&graph).try_wait().is_err());
+    /// assert!(!graph.is_running());
+    /// ```
+    pub fn run_graph(&self, graph: &JobGraph) -> JobFuture<()> {
+        let inner = self.inner();
+        inner.pending_dependents.fetch_add(graph.len(), Ordering::AcqRel);
+        return graph.start(inner.release_job_fn(), inner.settings.panic_policy);
+    }
+
+    /// Runs `future` to completion on the job threads. It is polled in a job every time it is woken, such as by an
+    /// awaited `JobFuture` completing, so no thread blocks while it waits. The returned future holds its output.
+    /// If it panics, the returned future holds the panic instead.
+    /// 
+    /// Whatever it awaits must eventually complete, since `wait()` and dropping the JobSystem wait for every spawned future.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let terrain = job_system.run_job(|| vec![1u32; 64]);
+    /// let props = job_system.run_job(|| vec![2u32; 16]);
+    /// let level = job_system.spawn_async(async move {
+    ///     let terrain = terrain.await;
+    ///     let props = props.await;
+    ///     terrain.len() + props.len()
+    /// });
+    /// assert_eq!(level.wait(), 80);
+    /// ```
+    /// A panic in the future, or in a job it awaits, is held by the returned future.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let shader = job_system.run_job(|| -> Vec<u8> { panic!("shader failed to compile") });
+    /// let material = job_system.spawn_async(async move { shader.await.len() });
+    /// assert_eq!(material.try_wait().unwrap_err().panic_message(), Some("shader failed to compile"));
+    /// ```
+    pub fn spawn_async<Fut>(&self, future: Fut) -> JobFuture<Fut::Output>
+    where Fut: Future + Send + 'static, Fut::Output: Send + 'static {
+        let inner = self.inner();
+        inner.pending_dependents.fetch_add(1, Ordering::AcqRel);
+        return AsyncTask::spawn(future, inner.queue_released_job_fn(), inner.finish_dependent_fn(), inner.settings.panic_policy);
+    }
+
+    /// Runs `func` with a `JobScope`, whose spawned jobs may borrow local data, such as from the caller's stack.
+    /// Waits for every spawned job to complete before returning, in the style of `std::thread::scope()`.
+    /// Jobs that have not started by then run on the calling thread, so it is fine to call from within a job.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if a spawned job panicked and its handle was not joined, once every job has completed.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let mut positions = vec![1.0f32; 1000];
+    /// let scale = 2.0f32;
+    /// let (left, right) = positions.split_at_mut(500);
+    /// let sum = job_system.scope(|s| {
+    ///     s.spawn(|| left.iter_mut().for_each(|position| *position *= scale));
+    ///     let right_sum = s.spawn(|| {
+    ///         right.iter_mut().for_each(|position| *position *= scale);
+    ///         right.iter().sum::<f32>()
+    ///     });
+    ///     right_sum.join()
+    /// });
+    /// assert_eq!(sum, 1000.0);
+    /// assert!(positions.iter().all(|position| *position == 2.0));
+    /// ```
+    /// A panicking job is resumed when joined, or when the scope ends.
+    /// ``` should_panic
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// job_system.scope(|s| {
+    ///     s.spawn(|| panic!("failed to decode asset"));
+    /// });
+    /// ```
+    pub fn scope<'env, F, T>(&'env self, func: F) -> T
+    where F: for<'scope> FnOnce(&'scope JobScope<'scope, 'env>) -> T {
+        assert!(self.inner.is_some(), "JobSystem is not initialized. Please call init()");
+        return scope::run_scope(self, func);
+    }
+
+    /// Queues an already made job, applying the `QueueFullPolicy`. Gives the job back if it is refused.
+    pub(crate) fn try_push_job(&self, job: JobContainer, priority: JobPriority) -> Result<(), (JobContainer, QueueFullError)> {
+        return self.inner().try_push_job(job, priority);
+    }
+
+    /// Calls `func` for every index in `range`, split into chunks of `grain` indices across the job threads,
+    /// and returns once every index is done. The calling thread works through chunks as well, so it is fine to call
+    /// from within a job. A `grain` of 0 picks one from the number of job threads.
+    /// 
+    /// Since it waits for completion, `func` can borrow from the caller, unlike the closures given to `run_job()`.
+    /// 
+    /// # Panics
+    /// 
+    /// Resumes the first panic of `func` once every chunk that started has finished. The remaining chunks are skipped.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use std::sync::atomic::{AtomicU64, Ordering};
+    /// let job_system = JobSystem::new_init(2);
+    /// let weights = vec![3u64; 10_000];
+    /// let total = AtomicU64::new(0);
+    /// job_system.parallel_for(0..weights.len(), 256, |index| { total.fetch_add(weights[index], Ordering::Relaxed); });
+    /// assert_eq!(total.load(Ordering::Relaxed), 30_000);
+    /// ```
+    /// A grain larger than the range, or a range ending near `usize::MAX`, still visits every index once.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// let job_system = JobSystem::new_init(2);
+    /// let sum = AtomicUsize::new(0);
+    /// job_system.parallel_for(5..10, usize::MAX, |index| { sum.fetch_add(index, Ordering::Relaxed); });
+    /// assert_eq!(sum.load(Ordering::Relaxed), 5 + 6 + 7 + 8 + 9);
+    ///
+    /// let count = AtomicUsize::new(0);
+    /// job_system.parallel_for(usize::MAX - 10..usize::MAX, 3, |_| { count.fetch_add(1, Ordering::Relaxed); });
+    /// assert_eq!(count.load(Ordering::Relaxed), 10);
+    /// ```
+    /// Chunks that no job thread has room for run on the calling thread, so full queues never make it fail.
+    /// ```
+    /// # use gk_types_rs::job_system::{system::JobSystem, settings::{JobSystemSettings, QueueFullPolicy}};
+    /// # use std::{thread, time::Duration, sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}}};
+    /// let settings = JobSystemSettings { queue_capacity: 1, queue_full_policy: QueueFullPolicy::Reject, ..Default::default() };
+    /// let job_system = JobSystem::new_init_with_settings(2, settings);
+    /// // Fills every queue, with the job threads held up by the first jobs.
+    /// let is_released = Arc::new(AtomicBool::new(false));
+    /// for _ in 0..2 {
+    ///     let is_released = is_released.clone();
+    ///     job_system.run_job(move || while !is_released.load(Ordering::Acquire) { thread::sleep(Duration::from_millis(1)) });
+    /// }
+    /// while job_system.try_run_job(|| ()).is_ok() {}
+    /// let count = AtomicUsize::new(0);
+    /// job_system.parallel_for(0..1000, 1, |_| { count.fetch_add(1, Ordering::Relaxed); });
+    /// assert_eq!(count.load(Ordering::Relaxed), 1000);
+    /// is_released.store(true, Ordering::Release);
+    /// ```
+    pub fn parallel_for<F>(&self, range: Range<usize>, grain: usize, func: F)
+    where F: Fn(usize) + Sync {
+        parallel::parallel_for(self, range, grain, &func);
+    }
+
+    /// Calls `func` on every element of `items` in chunks across the job threads, and returns once all are done.
+    /// See `parallel_for()`. Use `ParIterMut` to pick the chunk size.
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// let job_system = JobSystem::new_init(2);
+    /// let mut positions = vec![(0.0f32, 1.0f32); 4096];
+    /// let velocity = (2.0f32, -1.0f32);
+    /// job_system.parallel_for_each(&mut positions, |position| {
+    ///     position.0 += velocity.0;
+    ///     position.1 += velocity.1;
+    /// });
+    /// assert!(positions.iter().all(|position| *position == (2.0, 0.0)));
+    /// ```
+    pub fn parallel_for_each<T, F>(&self, items: &mut [T], func: F)
+    where T: Send, F: Fn(&mut T) + Sync {
+        ParIterMut::new(self, items).for_each(func);
+    }
+
+    /// Wait for all of the job threads to finish execution, sleeping until then.
     /// After wait is called, it can be assumed that there are no active jobs running.
     /// 
     /// Note: It is technically possible for there to be jobs executing, 
     /// if the jobs created more jobs that happened to be on earlier threads.
+    /// With `Scheduler::WorkStealing`, wait also covers jobs created by jobs.
+    /// Jobs from `run_job_after()` and `run_graph()` that are still waiting on prerequisites are waited for as well.
     /// ```
     /// # use gk_types_rs::job_system::system::JobSystem;
     /// let job_system = JobSystem::new_init(2);
@@ -230,12 +787,42 @@ impl JobSystem {
     /// job_system.run_job(|| std::thread::sleep(std::time::Duration::from_millis(10)));
     /// job_system.wait();
     /// ```
+    /// ```
+    /// # use gk_types_rs::job_system::system::JobSystem;
+    /// # use gk_types_rs::job_system::settings::{JobSystemSettings, Scheduler};
+    /// # use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    /// let settings = JobSystemSettings { scheduler: Scheduler::WorkStealing, ..Default::default() };
+    /// let job_system = Arc::new(JobSystem::new_init_with_settings(2, settings));
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    /// for _ in 0..10 {
+    ///     let (job_system_inner, counter) = (job_system.clone(), counter.clone());
+    ///     job_system.run_job(move || {
+    ///         for _ in 0..10 {
+    ///             let counter = counter.clone();
+    ///             job_system_inner.run_job(move || counter.fetch_add(1, Ordering::Relaxed));
+    ///         }
+    ///     });
+    /// }
+    /// job_system.wait();
+    /// assert_eq!(counter.load(Ordering::Relaxed), 100);
+    /// ```
     pub fn wait(&self) {
-        debug_assert!(self.is_initialized, "JobSystem is not initialized. Please call init()");
+        let inner = self.inner();
         thread::yield_now();
-        let inner = unsafe { (&mut *self.inner.get()).assume_init_mut() };
-        for job_thread in inner.threads.iter() {
-            job_thread.wait();
+        loop {
+            // Dependent jobs may be released onto threads that were already waited on, so wait again until none are left.
+            let has_pending_dependents = inner.pending_dependents.load(Ordering::Acquire) > 0;
+            for job_thread in inner.threads.iter() {
+                job_thread.wait();
+            }
+            if let Some(work_stealing) = &inner.work_stealing {
+                work_stealing.wait();
+            }
+            if !has_pending_dependents {
+                return;
+            }
+            let (lock, cvar) = &inner.dependents_released;
+            let _lock = cvar.wait_while(lock.lock().unwrap(), |_| inner.pending_dependents.load(Ordering::Acquire) > 0).unwrap();
         }
     }
 
@@ -251,19 +838,20 @@ impl JobSystem {
     /// assert_eq!(job_system.thread_count(), 4);
     /// ```
     pub fn thread_count(&self) -> usize {
-        return unsafe {
-            (&mut *self.inner.get()).assume_init_mut().thread_count
-        }
+        return self.inner().thread_count;
+    }
+
+    fn inner(&self) -> &Inner {
+        return self.inner.as_deref().expect("JobSystem is not initialized. Please call init()");
     }
 }
 
 impl Drop for JobSystem {
     fn drop(&mut self) {
-        if !self.is_initialized {
+        if self.inner.is_none() {
             return;
         }
-        thread::yield_now();
-        unsafe {(&mut *self.inner.get()).assume_init_drop()};
+        self.wait();
     }
 }
 
@@ -1,27 +1,55 @@
-#[allow(invalid_reference_casting)]
+use std::{cell::Cell, sync::Arc};
 
-use std::{sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Condvar, Mutex}, thread};
+use crate::allocator::thread_cache_allocator::ThreadCacheAllocator;
+use super::{sync::{thread, fence, AtomicBool, AtomicUsize, Condvar, Mutex, Ordering}, job_container::JobContainer, future::{JobFuture, WithinJobFuture, catch_job_panic}, cancellation::{CancellationToken, CancellableJob}, JobQueue, active_jobs::ActiveJobs,
+    settings::{JobSystemSettings, PanicPolicy, QueueFullPolicy, QueueFullError}, priority::{JobPriority, PRIORITY_COUNT}};
 
-use super::{job_container::JobContainer, future::{JobFuture, WithinJobFuture}, ring_queue::JobRingQueue, active_jobs::ActiveJobs};
+thread_local! {
+    static IS_JOB_THREAD: Cell<bool> = const { Cell::new(false) };
+}
 
-pub struct JobThread {
+/// Checks if the calling thread is a job thread, meaning it is running within a job.
+pub(crate) fn is_job_thread() -> bool {
+    return IS_JOB_THREAD.try_with(|is_job_thread| is_job_thread.get()).unwrap_or(false);
+}
+
+/// Marks the calling thread as a job thread. Called once by every job thread when it starts.
+pub(crate) fn mark_as_job_thread() {
+    IS_JOB_THREAD.with(|is_job_thread| is_job_thread.set(true));
+}
+
+// State shared by a `JobThread` and its thread.
+struct Shared {
+    // Set while the job thread has jobs to run, and cleared under the `cond_var` lock before it sleeps.
     is_executing: AtomicBool,
     is_pending_kill: AtomicBool,
-    should_execute: AtomicBool,
 
-    pub queued_job_count: AtomicUsize,
+    queued_job_count: AtomicUsize,
 
-    thread: Option<thread::JoinHandle<()>>,
-    cond_var: (Mutex<bool>, Condvar),
+    // The job thread sleeps on the condvar.
+    cond_var: (Mutex<SleepState>, Condvar),
+    // Notified under the `cond_var` lock whenever the job thread goes to sleep while threads are in `wait()`.
+    idle: Condvar,
 
-    queue: Mutex<JobRingQueue>,
-    active_work: Mutex<ActiveJobs>
+    settings: JobSystemSettings,
+    // One per `JobPriority`, most urgent first.
+    queues: [JobQueue; PRIORITY_COUNT],
+    // Threads waiting for room in a full queue, for `QueueFullPolicy::Block`.
+    blocked_producers: AtomicUsize,
+    // Notified whenever queued jobs are taken off the queue while producers are blocked.
+    queue_space: (Mutex<()>, Condvar)
 }
 
-struct JobThreadHandle(*mut JobThread);
+struct SleepState {
+    should_execute: bool,
+    // Threads sleeping on `Shared::idle`.
+    idle_waiter_count: usize
+}
 
-unsafe impl Send for JobThreadHandle {}
-unsafe impl Sync for JobThreadHandle {}
+pub struct JobThread {
+    shared: Arc<Shared>,
+    thread: Option<thread::JoinHandle<()>>
+}
 
 impl JobThread {
     /// Makes a new JobThread object. It is set to a valid state, 
@@ -31,81 +59,141 @@ impl JobThread {
     /// let job_thread = JobThread::new();
     /// ```
     pub fn new() -> Box<JobThread> {
-        let mut job_thread = Box::new(JobThread { 
+        return Self::new_with_settings(JobSystemSettings::default());
+    }
+
+    /// Makes a new JobThread object whose queue behaves according to `settings`. See `JobThread::new()`.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `settings.queue_capacity` or `settings.starvation_limit` is 0.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use gk_types_rs::job_system::thread::JobThread;
+    /// # use gk_types_rs::job_system::settings::{JobSystemSettings, QueueFullPolicy};
+    /// let job_thread = JobThread::new_with_settings(JobSystemSettings { queue_capacity: 2, queue_full_policy: QueueFullPolicy::Reject, ..Default::default() });
+    /// assert!(job_thread.try_queue_job(|| 1).is_ok());
+    /// assert!(job_thread.try_queue_job(|| 2).is_ok());
+    /// // Not executing yet, so the queue is full.
+    /// assert_eq!(job_thread.try_queue_job(|| 3).err().unwrap().capacity, 2);
+    /// ```
+    /// A starvation limit of 0 would run the least urgent jobs first, so it is refused.
+    /// ``` should_panic
+    /// # use gk_types_rs::job_system::thread::JobThread;
+    /// # use gk_types_rs::job_system::settings::JobSystemSettings;
+    /// let job_thread = JobThread::new_with_settings(JobSystemSettings { starvation_limit: 0, ..Default::default() });
+    /// ```
+    pub fn new_with_settings(settings: JobSystemSettings) -> Box<JobThread> {
+        settings.assert_valid();
+        let shared = Arc::new(Shared { 
             is_executing: AtomicBool::new(false), 
             is_pending_kill: AtomicBool::new(false), 
-            should_execute: AtomicBool::new(false),
             queued_job_count: AtomicUsize::new(0), 
-            cond_var: (Mutex::new(true), Condvar::new()), 
-            queue: Mutex::new(JobRingQueue::new()), 
-            active_work: Mutex::new(ActiveJobs::new()),
-            thread: Option::None, 
+            cond_var: (Mutex::new(SleepState { should_execute: false, idle_waiter_count: 0 }), Condvar::new()), 
+            idle: Condvar::new(),
+            settings,
+            queues: std::array::from_fn(|_| JobQueue::new(settings.queue_capacity)), 
+            blocked_producers: AtomicUsize::new(0),
+            queue_space: (Mutex::new(()), Condvar::new())
         });
 
-        let thread_ptr: JobThreadHandle = JobThreadHandle(&mut *job_thread as *mut JobThread);
-        
-        job_thread.thread = Option::Some(
-            thread::spawn(move || {
-                let _ = &thread_ptr; // Will allow the pointer shenanigans
-                unsafe {
-                    while (*thread_ptr.0).is_pending_kill.load(Ordering::Acquire) == false {
-                        let (lock, cvar) = &mut (*thread_ptr.0).cond_var;
-
-                        let count = {
-                            // scoped to release lock
-                            (*thread_ptr.0).queue.lock().unwrap().length                      
-                        };
-                        if count > 0 {
-                            (*thread_ptr.0).execute_queued_jobs();
-                            continue;
-                        }
-
-                        (*thread_ptr.0).is_executing.store(false, Ordering::Release);
-                        {
-                            let _result = cvar.wait_while(
-                                lock.lock().unwrap(),
-                                |_| (*thread_ptr.0).should_execute.load(Ordering::Relaxed) == false
-                            ).unwrap();
-                        }
-                        (*thread_ptr.0).execute_queued_jobs();
-                    }
-                }
-            })
-        ); 
+        let thread_shared = shared.clone();
+        let thread = thread::spawn(move || {
+            mark_as_job_thread();
+            // Only this thread consumes the queues, so the collected jobs never leave it.
+            let mut active_work = ActiveJobs::new(settings.starvation_limit);
+            thread_shared.run(&mut active_work);
+        });
 
-        return job_thread;
+        return Box::new(JobThread { shared, thread: Option::Some(thread) });
     }
 
     /// Adds a job to this job thread's queue, returning a future for completion.
     /// Will not execute the queue until JobThread::execute() is called.
+    /// If the queue is full, it grows, blocks or panics according to the thread's `QueueFullPolicy`.
+    /// A blocking queue starts executing so that it can make room.
     /// ```
     /// # use gk_types_rs::job_system::thread::JobThread;
     /// # use gk_types_rs::job_system::future::JobFuture;
-    /// let mut job_thread = JobThread::new();
+    /// let job_thread = JobThread::new();
     /// // Will not execute until JobThread::execute() is called
     /// let future = job_thread.queue_job(|| 10);
     /// ```
-    pub fn queue_job<T, F>(&mut self, mut func: F) -> JobFuture<T>
-    where T: 'static, F: FnMut() -> T + 'static {
+    pub fn queue_job<T, F>(&self, func: F) -> JobFuture<T>
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
+        return self.try_queue_job(func).expect("Job queue is full");
+    }
+
+    /// Adds a job to this job thread's queue, returning a future for completion, or an error if the queue is full
+    /// and the `QueueFullPolicy` is `Reject`. See `queue_job()`.
+    pub fn try_queue_job<T, F>(&self, func: F) -> Result<JobFuture<T>, QueueFullError>
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
+        return self.try_queue_job_with_priority(JobPriority::Normal, func);
+    }
+
+    /// Adds a job to this job thread's queue for `priority`, returning a future for completion.
+    /// More urgent jobs run first, but a waiting job runs anyway once the thread's starvation limit of more urgent
+    /// jobs have run ahead of it. Jobs queued with `queue_job()` are `JobPriority::Normal`.
+    /// ```
+    /// # use gk_types_rs::job_system::thread::JobThread;
+    /// # use gk_types_rs::job_system::priority::JobPriority;
+    /// # use std::sync::{Arc, Mutex};
+    /// let job_thread = JobThread::new();
+    /// let order = Arc::new(Mutex::new(Vec::new()));
+    /// let (order_background, order_critical) = (order.clone(), order.clone());
+    /// job_thread.queue_job_with_priority(JobPriority::Background, move || order_background.lock().unwrap().push("decode"));
+    /// job_thread.queue_job_with_priority(JobPriority::Critical, move || order_critical.lock().unwrap().push("frame"));
+    /// // Both are queued before executing, so the critical job runs first.
+    /// job_thread.execute();
+    /// job_thread.wait();
+    /// assert_eq!(*order.lock().unwrap(), vec!["frame", "decode"]);
+    /// ```
+    pub fn queue_job_with_priority<T, F>(&self, priority: JobPriority, func: F) -> JobFuture<T>
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
+        return self.try_queue_job_with_priority(priority, func).expect("Job queue is full");
+    }
+
+    /// Adds a job to this job thread's queue for `priority`, returning a future for completion, or an error if that
+    /// queue is full and the `QueueFullPolicy` is `Reject`. See `queue_job_with_priority()`.
+    pub fn try_queue_job_with_priority<T, F>(&self, priority: JobPriority, func: F) -> Result<JobFuture<T>, QueueFullError>
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
+        let (wait_future, job) = Self::make_job(self.shared.settings.panic_policy, func);
+        return self.shared.try_push_job(job, priority).map(|_| wait_future).map_err(|(_, queue_full)| queue_full);
+    }
+
+    /// Wraps `func` in a job that sets the returned future, handling a panic according to `panic_policy`.
+    pub(crate) fn make_job<T, F>(panic_policy: PanicPolicy, mut func: F) -> (JobFuture<T>, JobContainer)
+    where T: Send + 'static, F: FnMut() -> T + Send + 'static {
         let (wait_future, in_job_future) = WithinJobFuture::<T>::new();
         let job = JobContainer::new(move ||
-            in_job_future.set(func())
+            in_job_future.set(catch_job_panic(panic_policy, &mut func))
         );
+        return (wait_future, job);
+    }
 
-        {
-            let mut queue_lock = self.queue.lock().unwrap();
-            (*queue_lock).push(job);
-            self.queued_job_count.fetch_add(1, Ordering::Release);
-        }
+    /// Wraps `func` like `make_job()`, except cancelling `token` before the job starts drops `func`, and sets the returned
+    /// future to `JobError::Cancelled`, right away. If `token` is cancelled while `func` runs, its result is discarded.
+    pub(crate) fn make_cancellable_job<T, F>(panic_policy: PanicPolicy, token: CancellationToken, func: F) -> (JobFuture<T>, JobContainer)
+    where T: Send + 'static, F: FnOnce(&CancellationToken) -> T + Send + 'static {
+        let (wait_future, in_job_future) = WithinJobFuture::<T>::new();
+        let cancellable = CancellableJob::new(func, in_job_future, token, panic_policy);
+        let job = JobContainer::new(move || cancellable.run());
+        return (wait_future, job);
+    }
 
-        return wait_future;
+    /// Pushes `job` onto the queue for `priority` without locking, applying the `QueueFullPolicy` if it is full.
+    /// Gives the job back if it is refused, so it can be queued elsewhere.
+    pub(crate) fn try_push_job(&self, job: JobContainer, priority: JobPriority) -> Result<(), (JobContainer, QueueFullError)> {
+        return self.shared.try_push_job(job, priority);
     }
 
     /// Executes the jobs that are queued.
     /// ```
     /// # use gk_types_rs::job_system::thread::JobThread;
     /// # use gk_types_rs::job_system::future::JobFuture;
-    /// let mut job_thread = JobThread::new();
+    /// let job_thread = JobThread::new();
     /// let future1 = job_thread.queue_job(|| 10);
     /// let future2 = job_thread.queue_job(|| 20);
     /// // Jobs will execute here
@@ -115,80 +203,185 @@ impl JobThread {
     /// assert_eq!(num1, 10);
     /// assert_eq!(num2, 20);
     /// ```
-    pub fn execute(&mut self) {
-        if self.is_executing.load(Ordering::Acquire) == true { 
-            // should already be looping the execution, in which if it has any queued jobs, it will execute them.
-            return;
-        }
-        self.should_execute.store(true, Ordering::Release);
-        self.cond_var.1.notify_one();
-        self.is_executing.store(true, Ordering::Release);
+    pub fn execute(&self) {
+        self.shared.execute();
     }
 
-    /// Waits until execution of the entire queue is completed. 
+    /// Waits until execution of the entire queue is completed, sleeping until then.
     /// If more jobs are queued while it's executing, wait will continue waiting.
     /// ```
     /// # use gk_types_rs::job_system::thread::JobThread;
     /// # use std::{thread, time::Duration};
-    /// let mut job_thread = JobThread::new();
+    /// let job_thread = JobThread::new();
     /// job_thread.queue_job(|| thread::sleep(Duration::from_millis(5)));
     /// job_thread.queue_job(|| thread::sleep(Duration::from_millis(5)));
     /// job_thread.execute();
     /// job_thread.wait();
     /// ```
     pub fn wait(&self) {
-        while self.is_executing.load(Ordering::Acquire) == true {
-            thread::yield_now();
-        }
+        let mut sleep_state = self.shared.cond_var.0.lock().unwrap();
+        sleep_state.idle_waiter_count += 1;
+        sleep_state = self.shared.idle.wait_while(sleep_state, |_| self.shared.is_executing.load(Ordering::Acquire)).unwrap();
+        sleep_state.idle_waiter_count -= 1;
     }
 
     /// Atomically get the number of jobs queued. Does not get the queue mutex.
     /// ```
     /// # use gk_types_rs::job_system::thread::JobThread;
     /// # use std::{thread, time::Duration};
-    /// let mut job_thread = JobThread::new();
+    /// let job_thread = JobThread::new();
     /// for i in 0..10 {
     ///     job_thread.queue_job(move || i);
     /// }
     /// assert_eq!(job_thread.queued_count(), 10);
     /// ```
     pub fn queued_count(&self) -> usize {
-        return self.queued_job_count.load(Ordering::Acquire);
+        return self.shared.queued_job_count.load(Ordering::Acquire);
     }
 
     /// Atomically check if the job thread is executing. Useful for optimal scheduling.
     /// ```
     /// # use gk_types_rs::job_system::thread::JobThread;
     /// # use std::{thread, time::Duration};
-    /// let mut job_thread = JobThread::new();
+    /// let job_thread = JobThread::new();
     /// job_thread.queue_job(|| thread::sleep(Duration::from_millis(5)));
     /// job_thread.execute();
     /// assert!(job_thread.is_executing());
     /// ```
     pub fn is_executing(&self) -> bool {
-        return self.is_executing.load(Ordering::Acquire);
+        return self.shared.is_executing.load(Ordering::Acquire);
+    }
+}
+
+impl Shared {
+    // See `JobThread::try_push_job()`.
+    fn try_push_job(&self, mut job: JobContainer, priority: JobPriority) -> Result<(), (JobContainer, QueueFullError)> {
+        let queue = &self.queues[priority.index()];
+        // Counted before pushing, so the job thread never takes more jobs off the count than were added.
+        self.queued_job_count.fetch_add(1, Ordering::Release);
+        loop {
+            if !queue.has_overflowed() {
+                match queue.try_push(job) {
+                    Ok(()) => return Ok(()),
+                    Err(refused_job) => job = refused_job
+                }
+            }
+            match self.settings.queue_full_policy {
+                QueueFullPolicy::Grow => {
+                    queue.push_overflow(job);
+                    return Ok(());
+                },
+                QueueFullPolicy::Block if is_job_thread() => {
+                    queue.push_overflow(job);
+                    return Ok(());
+                },
+                QueueFullPolicy::Block => match self.wait_for_queue_space(queue, job) {
+                    None => return Ok(()),
+                    Some(refused_job) => job = refused_job
+                },
+                QueueFullPolicy::Reject => {
+                    self.queued_job_count.fetch_sub(1, Ordering::Release);
+                    return Err((job, QueueFullError { capacity: queue.capacity() }));
+                }
+            }
+        }
+    }
+
+    /// Pushes `job` onto `queue` once the job thread has taken jobs off it, blocking until then.
+    /// Gives the job back if the queue filled up again before it could be pushed.
+    fn wait_for_queue_space(&self, queue: &JobQueue, job: JobContainer) -> Option<JobContainer> {
+        self.execute();
+        let (space_lock, space_cvar) = &self.queue_space;
+        self.blocked_producers.fetch_add(1, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+        let lock = space_lock.lock().unwrap();
+        let refused_job = match queue.try_push(job) {
+            Ok(()) => None,
+            Err(refused_job) => {
+                let _lock = space_cvar.wait(lock).unwrap();
+                Some(refused_job)
+            }
+        };
+        self.blocked_producers.fetch_sub(1, Ordering::SeqCst);
+        return refused_job;
     }
 
-    fn execute_queued_jobs(&mut self) {
-        let mut active_lock = self.active_work.lock().unwrap();
-        {
-            let mut queue_lock = self.queue.lock().unwrap();
-            self.queued_job_count.store(0, Ordering::Release);
-            (*active_lock).collect_jobs(&mut *queue_lock);
-            // queue lock is unlocked here.
+    fn execute(&self) {
+        // Pairs with the fence in `run()`. Either the job thread sees the queued jobs before sleeping,
+        // or this sees that it is not executing and wakes it.
+        fence(Ordering::SeqCst);
+        if self.is_executing.load(Ordering::SeqCst)
+            || self.is_executing.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() { 
+            // should already be looping the execution, in which if it has any queued jobs, it will execute them.
+            return;
         }
-        (*active_lock).invoke_all_jobs();
+        // Only the thread that set `is_executing` wakes the job thread.
+        self.cond_var.0.lock().unwrap().should_execute = true;
+        self.cond_var.1.notify_one();
+    }
+
+    // Only called by the job thread. Skips slots a producer has claimed but not written yet, since queued jobs
+    // are only run after `execute()` is called, and that wakes this thread once the job is written.
+    fn has_queued_jobs(&self) -> bool {
+        return self.queues.iter().any(|queue| queue.has_collectable_jobs());
     }
 
+    // Loop of the job thread, until the JobThread is dropped.
+    fn run(&self, active_work: &mut ActiveJobs) {
+        let (lock, cvar) = &self.cond_var;
+        loop {
+            self.execute_queued_jobs(active_work);
+            // Before taking the lock, since it may give many blocks back to their allocators.
+            ThreadCacheAllocator::release_stale_caches();
+
+            let mut sleep_state = lock.lock().unwrap();
+            if self.is_pending_kill.load(Ordering::Acquire) {
+                return;
+            }
+            self.is_executing.store(false, Ordering::SeqCst);
+            fence(Ordering::SeqCst);
+            if self.has_queued_jobs() {
+                // Queued after the last collection, by a producer that saw this thread still executing.
+                self.is_executing.store(true, Ordering::SeqCst);
+                continue;
+            }
+            if sleep_state.idle_waiter_count > 0 {
+                self.idle.notify_all();
+            }
+            sleep_state = cvar.wait_while(sleep_state, |sleep_state| !sleep_state.should_execute).unwrap();
+            sleep_state.should_execute = false;
+        }
+    }
+
+    fn execute_queued_jobs(&self, active_work: &mut ActiveJobs) {
+        loop {
+            // Collects again before every job, so newly queued urgent jobs go ahead of less urgent collected ones.
+            // Only the job thread, which owns `active_work`, consumes the queues.
+            let collected_count = unsafe { active_work.collect_jobs(&self.queues) };
+            if collected_count > 0 {
+                self.queued_job_count.fetch_sub(collected_count, Ordering::Release);
+                fence(Ordering::SeqCst);
+                if self.blocked_producers.load(Ordering::SeqCst) > 0 {
+                    let _lock = self.queue_space.0.lock().unwrap();
+                    self.queue_space.1.notify_all();
+                }
+            }
+
+            match active_work.take_next_job() {
+                Some(mut job) => job.invoke(),
+                None => return
+            }
+        }
+    }
 }
 
 impl Drop for JobThread {
     fn drop(&mut self) {
         self.wait();
-        self.is_pending_kill.store(true, Ordering::SeqCst);
-        self.queued_job_count.store(isize::MAX as usize, Ordering::Release); // some insanely huge value that couldn't happen naturally. Not usize::MAX to not cause issues with incrementing
-        self.should_execute.store(true, Ordering::Release);
-        self.cond_var.1.notify_one();
+        self.shared.is_pending_kill.store(true, Ordering::SeqCst);
+        self.shared.queued_job_count.store(isize::MAX as usize, Ordering::Release); // some insanely huge value that couldn't happen naturally. Not usize::MAX to not cause issues with incrementing
+        self.shared.cond_var.0.lock().unwrap().should_execute = true;
+        self.shared.cond_var.1.notify_one();
         let thread = std::mem::take(&mut self.thread).unwrap();
         thread.join().expect("failed to join job thread");
 
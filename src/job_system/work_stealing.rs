@@ -0,0 +1,212 @@
+use std::{cell::Cell, sync::{atomic::{fence, AtomicBool, AtomicUsize, Ordering}, Arc, Condvar, Mutex}, thread};
+use crate::allocator::thread_cache_allocator::ThreadCacheAllocator;
+use super::{deque::{ChaseLevDeque, Steal}, job_container::JobContainer, ring_queue::JobRingQueue,
+    settings::{JobSystemSettings, QueueFullPolicy, QueueFullError}, thread::{is_job_thread, mark_as_job_thread},
+    priority::{JobPriority, StarvationGuard, PRIORITY_COUNT}};
+
+thread_local! {
+    // Shared state address and worker index of the pool the calling thread works for, if any.
+    static CURRENT_WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+struct Shared {
+    // Every worker has a deque per `JobPriority`, as does the injector, most urgent first.
+    deques: Box<[[ChaseLevDeque; PRIORITY_COUNT]]>,
+    injectors: [Mutex<JobRingQueue>; PRIORITY_COUNT],
+    // Notified whenever a job is taken off the injector of the same priority, for `QueueFullPolicy::Block`.
+    injector_space: [Condvar; PRIORITY_COUNT],
+    queue_full_policy: QueueFullPolicy,
+    starvation_limit: usize,
+    // Jobs waiting in the deques and injector, per priority. Counted before pushing, so sleeping workers never miss one.
+    queued: [AtomicUsize; PRIORITY_COUNT],
+    // Jobs queued or running, for `wait()`.
+    pending: AtomicUsize,
+    sleepers: AtomicUsize,
+    sleep_lock: Mutex<()>,
+    wake: Condvar,
+    // Notified under the sleep lock once `pending` reaches 0, for `wait()`.
+    idle: Condvar,
+    is_pending_kill: AtomicBool
+}
+
+impl Shared {
+    /// Takes a job of the priority picked by `starvation_guard`, or of any priority if there are none of it left.
+    fn find_job(&self, worker_index: usize, starvation_guard: &mut StarvationGuard) -> Option<Box<JobContainer>> {
+        let has_work = std::array::from_fn(|priority_index| self.queued[priority_index].load(Ordering::Acquire) > 0);
+        let picked = starvation_guard.next_priority(has_work)?;
+        let job = self.find_job_of_priority(worker_index, picked)
+            .or_else(|| (0..PRIORITY_COUNT).find_map(|priority_index| self.find_job_of_priority(worker_index, priority_index)))?;
+        return Some(job);
+    }
+
+    fn find_job_of_priority(&self, worker_index: usize, priority_index: usize) -> Option<Box<JobContainer>> {
+        let job = self.take_local(worker_index, priority_index)
+            .or_else(|| self.take_injected(priority_index))
+            .or_else(|| self.steal(worker_index, priority_index))?;
+        self.queued[priority_index].fetch_sub(1, Ordering::AcqRel);
+        return Some(job);
+    }
+
+    fn take_local(&self, worker_index: usize, priority_index: usize) -> Option<Box<JobContainer>> {
+        let job = unsafe { self.deques[worker_index][priority_index].pop() };
+        if job.is_null() {
+            return None;
+        }
+        return Some(unsafe { Box::from_raw(job) });
+    }
+
+    fn take_injected(&self, priority_index: usize) -> Option<Box<JobContainer>> {
+        let mut injector_lock = self.injectors[priority_index].lock().unwrap();
+        let job = (*injector_lock).pop()?;
+        self.injector_space[priority_index].notify_one();
+        return Some(Box::new(job));
+    }
+
+    fn steal(&self, worker_index: usize, priority_index: usize) -> Option<Box<JobContainer>> {
+        let deque_count = self.deques.len();
+        loop {
+            let mut should_retry = false;
+            for i in 1..deque_count {
+                match self.deques[(worker_index + i) % deque_count][priority_index].steal() {
+                    Steal::Success(job) => return Some(unsafe { Box::from_raw(job) }),
+                    Steal::Retry => should_retry = true,
+                    Steal::Empty => ()
+                }
+            }
+            if !should_retry {
+                return None;
+            }
+        }
+    }
+
+    fn has_work(&self) -> bool {
+        return self.queued.iter().any(|queued| queued.load(Ordering::SeqCst) > 0);
+    }
+
+    fn wake_one(&self) {
+        fence(Ordering::SeqCst);
+        if self.sleepers.load(Ordering::SeqCst) > 0 {
+            let _sleep_lock = self.sleep_lock.lock().unwrap();
+            self.wake.notify_one();
+        }
+    }
+
+    fn sleep(&self) {
+        self.sleepers.fetch_add(1, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+        {
+            let sleep_lock = self.sleep_lock.lock().unwrap();
+            // Work queued after the check notifies under the sleep lock, so it cannot be missed.
+            if !self.has_work() && !self.is_pending_kill.load(Ordering::Acquire) {
+                let _sleep_lock = self.wake.wait(sleep_lock).unwrap();
+            }
+        }
+        self.sleepers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Job threads that take jobs from a shared injector queue, push jobs run from within jobs onto their own
+/// deque, and steal from each other when idle. Used by `JobSystem` for `Scheduler::WorkStealing`.
+pub(crate) struct WorkStealingPool {
+    shared: Arc<Shared>,
+    threads: Vec<thread::JoinHandle<()>>
+}
+
+impl WorkStealingPool {
+    pub(crate) fn new(thread_count: usize, settings: JobSystemSettings) -> Self {
+        settings.assert_valid();
+        let shared = Arc::new(Shared {
+            deques: (0..thread_count).map(|_| std::array::from_fn(|_| ChaseLevDeque::new())).collect(),
+            injectors: std::array::from_fn(|_| Mutex::new(JobRingQueue::new(settings.queue_capacity * thread_count))),
+            injector_space: std::array::from_fn(|_| Condvar::new()),
+            queue_full_policy: settings.queue_full_policy,
+            starvation_limit: settings.starvation_limit,
+            queued: std::array::from_fn(|_| AtomicUsize::new(0)),
+            pending: AtomicUsize::new(0),
+            sleepers: AtomicUsize::new(0),
+            sleep_lock: Mutex::new(()),
+            wake: Condvar::new(),
+            idle: Condvar::new(),
+            is_pending_kill: AtomicBool::new(false)
+        });
+
+        let threads = (0..thread_count).map(|worker_index| {
+            let shared = shared.clone();
+            thread::spawn(move || Self::worker_loop(shared, worker_index))
+        }).collect();
+        return WorkStealingPool { shared, threads };
+    }
+
+    /// Pushes `job` onto the calling worker's deque for `priority` when called from within one of this pool's jobs,
+    /// or onto the shared injector for `priority` otherwise.
+    pub(crate) fn try_push_job(&self, job: JobContainer, priority: JobPriority) -> Result<(), (JobContainer, QueueFullError)> {
+        let shared_address = Arc::as_ptr(&self.shared) as usize;
+        let priority_index = priority.index();
+        match CURRENT_WORKER.try_with(|worker| worker.get()).ok().flatten() {
+            Some((address, worker_index)) if address == shared_address => {
+                self.shared.pending.fetch_add(1, Ordering::AcqRel);
+                self.shared.queued[priority_index].fetch_add(1, Ordering::SeqCst);
+                unsafe { self.shared.deques[worker_index][priority_index].push(Box::into_raw(Box::new(job))) };
+            },
+            _ => {
+                let mut injector_lock = self.shared.injectors[priority_index].lock().unwrap();
+                while (*injector_lock).is_full() {
+                    match self.shared.queue_full_policy {
+                        QueueFullPolicy::Grow => (*injector_lock).grow(),
+                        QueueFullPolicy::Block if is_job_thread() => (*injector_lock).grow(),
+                        QueueFullPolicy::Block => injector_lock = self.shared.injector_space[priority_index].wait(injector_lock).unwrap(),
+                        QueueFullPolicy::Reject => return Err((job, QueueFullError { capacity: (*injector_lock).capacity() }))
+                    }
+                }
+                self.shared.pending.fetch_add(1, Ordering::AcqRel);
+                self.shared.queued[priority_index].fetch_add(1, Ordering::SeqCst);
+                (*injector_lock).push(job);
+            }
+        }
+        self.shared.wake_one();
+        return Ok(());
+    }
+
+    /// Sleeps until every queued job has finished, including jobs queued by those jobs.
+    pub(crate) fn wait(&self) {
+        let _sleep_lock = self.shared.idle.wait_while(
+            self.shared.sleep_lock.lock().unwrap(),
+            |_| self.shared.pending.load(Ordering::Acquire) > 0
+        ).unwrap();
+    }
+
+    fn worker_loop(shared: Arc<Shared>, worker_index: usize) {
+        mark_as_job_thread();
+        CURRENT_WORKER.with(|worker| worker.set(Some((Arc::as_ptr(&shared) as usize, worker_index))));
+        let mut starvation_guard = StarvationGuard::new(shared.starvation_limit);
+        loop {
+            if let Some(mut job) = shared.find_job(worker_index, &mut starvation_guard) {
+                job.invoke();
+                if shared.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+                    let _sleep_lock = shared.sleep_lock.lock().unwrap();
+                    shared.idle.notify_all();
+                }
+                continue;
+            }
+            if shared.is_pending_kill.load(Ordering::Acquire) {
+                return;
+            }
+            ThreadCacheAllocator::release_stale_caches();
+            shared.sleep();
+        }
+    }
+}
+
+impl Drop for WorkStealingPool {
+    fn drop(&mut self) {
+        // Workers only exit once they find no more work, so every queued job still runs.
+        self.shared.is_pending_kill.store(true, Ordering::Release);
+        {
+            let _sleep_lock = self.shared.sleep_lock.lock().unwrap();
+            self.shared.wake.notify_all();
+        }
+        for thread in self.threads.drain(..) {
+            thread.join().expect("failed to join job thread");
+        }
+    }
+}
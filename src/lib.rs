@@ -1,5 +1,6 @@
 #![feature(inline_const)]
 #![feature(stdsimd)]
+#![feature(allocator_api)]
 
 pub mod allocator;
 pub mod cpu_features;